@@ -12,14 +12,12 @@ echo "Deleting User Identities..."
 CANISTER=$(dfx canister id DDate_backend)
 echo "Canister ID: $CANISTER"
 
-# Use the anonymous identity
-dfx identity use "$ANONYMOUS_IDENTITY"
-
 while IFS=: read -r USER_IDENTITY USER_ID; do
-    echo "Using anonymous identity to delete account with user ID $USER_ID for identity $USER_IDENTITY"
-    
-    # Delete the account with the user ID
-    dfx canister call $CANISTER delete_an_account "(\"$USER_ID\")"
+    echo "Using controller identity to delete account with user ID $USER_ID for identity $USER_IDENTITY"
+
+    # Bulk deletion goes through the admin endpoint, which the controller identity can call
+    dfx identity use "$CONTROLLER_IDENTITY"
+    dfx canister call $CANISTER admin_delete_account "(\"$USER_ID\")"
     
     # Switch to the anonymous identity again to remove the user identity
     dfx identity use "$ANONYMOUS_IDENTITY"
@@ -0,0 +1,228 @@
+use std::cell::RefCell;
+use std::time::Duration;
+
+use crate::cascade::{after, over_budget, Progress};
+use crate::state_handler::{mutate_state, State};
+
+const NANOS_PER_DAY: u64 = 24 * 60 * 60 * 1_000_000_000;
+const AGE_REFRESH_INTERVAL: Duration = Duration::from_secs(24 * 60 * 60);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Date {
+    pub year: u32,
+    pub month: u32,
+    pub day: u32,
+}
+
+// A refresh in progress; it walks the profiles in key order over as many ticks as it needs.
+struct AgeRefresh {
+    today: Date,
+    // Last profile checked; the next tick resumes after it.
+    cursor: Option<String>,
+    refreshed: u64,
+}
+
+thread_local! {
+    static AGE_REFRESH: RefCell<Option<AgeRefresh>> = const { RefCell::new(None) };
+}
+
+// What profile validation needs to check a date of birth: the current UTC date and the configured minimum age.
+#[derive(Debug, Clone, Copy)]
+pub struct AgePolicy {
+    pub today: Date,
+    pub min_age: u64,
+}
+
+fn is_leap_year(year: u32) -> bool {
+    (year.is_multiple_of(4) && !year.is_multiple_of(100)) || year.is_multiple_of(400)
+}
+
+fn days_in_month(year: u32, month: u32) -> u32 {
+    match month {
+        1 | 3 | 5 | 7 | 8 | 10 | 12 => 31,
+        4 | 6 | 9 | 11 => 30,
+        2 if is_leap_year(year) => 29,
+        2 => 28,
+        _ => 0,
+    }
+}
+
+impl Date {
+    // Parses a calendar date in the `YYYY-MM-DD` form sent by the frontend's date input.
+    pub fn parse(value: &str) -> Option<Date> {
+        let mut parts = value.trim().splitn(3, '-');
+        let (year, month, day) = (parts.next()?, parts.next()?, parts.next()?);
+        if year.len() != 4 || month.len() != 2 || day.len() != 2 {
+            return None;
+        }
+        let date = Date {
+            year: year.parse().ok()?,
+            month: month.parse().ok()?,
+            day: day.parse().ok()?,
+        };
+        if date.day == 0 || date.day > days_in_month(date.year, date.month) {
+            return None;
+        }
+        Some(date)
+    }
+
+    // Converts IC time (nanoseconds since the Unix epoch) to a UTC date.
+    pub fn from_nanos(nanos: u64) -> Date {
+        // Howard Hinnant's days-to-civil algorithm, restricted to dates after 1970.
+        let z = nanos / NANOS_PER_DAY + 719_468;
+        let era = z / 146_097;
+        let doe = z - era * 146_097;
+        let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+        let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+        let mp = (5 * doy + 2) / 153;
+        let day = doy - (153 * mp + 2) / 5 + 1;
+        let month = if mp < 10 { mp + 3 } else { mp - 9 };
+        let year = yoe + era * 400 + u64::from(month <= 2);
+        Date {
+            year: year as u32,
+            month: month as u32,
+            day: day as u32,
+        }
+    }
+
+    // Completed years between `self` and `today`, or `None` if `self` is in the future.
+    pub fn age_on(&self, today: Date) -> Option<u64> {
+        if *self > today {
+            return None;
+        }
+        let had_birthday = (today.month, today.day) >= (self.month, self.day);
+        Some(u64::from(today.year - self.year) - u64::from(!had_birthday))
+    }
+}
+
+// Age derived from a stored `dob`, or `None` when the value is missing or not a valid past date.
+pub fn age_from_dob(dob: &Option<String>, today: Date) -> Option<u64> {
+    dob.as_deref().and_then(Date::parse).and_then(|dob| dob.age_on(today))
+}
+
+impl State {
+    pub fn age_policy(&self, now: u64) -> AgePolicy {
+        AgePolicy {
+            today: Date::from_nanos(now),
+            min_age: self.config().min_age(),
+        }
+    }
+
+    // Rewrites the profiles after the run's cursor whose stored age no longer matches their date of birth,
+    // until the tick's budget runs out. Age is not owner-editable, so this leaves `version` alone.
+    fn refresh_ages(&mut self, run: &mut AgeRefresh) -> Progress {
+        loop {
+            let next = self.user_profiles.range(after(&run.cursor)).next();
+            let Some((user_id, profile)) = next else {
+                return Progress::Done;
+            };
+            if age_from_dob(&profile.params.dob, run.today).is_some_and(|age| profile.params.age != Some(age)) {
+                // `save_profile` derives the new age from `dob`.
+                self.save_profile(user_id.clone(), profile);
+                run.refreshed += 1;
+            }
+            run.cursor = Some(user_id);
+            if over_budget() {
+                return Progress::OutOfBudget;
+            }
+        }
+    }
+}
+
+fn run_age_refresh_tick() {
+    let Some(mut run) = AGE_REFRESH.with(|refresh| refresh.borrow_mut().take()) else {
+        return;
+    };
+    match mutate_state(|state| state.refresh_ages(&mut run)) {
+        Progress::Done => ic_cdk::println!("Refreshed the age of {} profiles", run.refreshed),
+        Progress::OutOfBudget => {
+            AGE_REFRESH.with(|refresh| *refresh.borrow_mut() = Some(run));
+            ic_cdk_timers::set_timer(Duration::ZERO, run_age_refresh_tick);
+        }
+    }
+}
+
+// Starts a refresh unless the previous one is still working through the profiles.
+fn refresh_ages() {
+    let started = AGE_REFRESH.with(|refresh| {
+        let mut refresh = refresh.borrow_mut();
+        if refresh.is_some() {
+            return false;
+        }
+        *refresh = Some(AgeRefresh {
+            today: Date::from_nanos(ic_cdk::api::time()),
+            cursor: None,
+            refreshed: 0,
+        });
+        true
+    });
+    if started {
+        ic_cdk_timers::set_timer(Duration::ZERO, run_age_refresh_tick);
+    }
+}
+
+pub fn start_age_refresh_timer() {
+    ic_cdk_timers::set_timer(Duration::ZERO, refresh_ages);
+    ic_cdk_timers::set_timer_interval(AGE_REFRESH_INTERVAL, refresh_ages);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(year: u32, month: u32, day: u32) -> Date {
+        Date { year, month, day }
+    }
+
+    #[test]
+    fn parses_calendar_dates() {
+        assert_eq!(Date::parse("1990-07-15"), Some(date(1990, 7, 15)));
+        assert_eq!(Date::parse(" 2000-02-29 "), Some(date(2000, 2, 29)));
+        assert_eq!(Date::parse("1900-02-29"), None);
+        assert_eq!(Date::parse("2023-02-29"), None);
+        assert_eq!(Date::parse("2023-04-31"), None);
+        assert_eq!(Date::parse("2023-13-01"), None);
+        assert_eq!(Date::parse("2023-01-00"), None);
+        assert_eq!(Date::parse("2023-1-01"), None);
+        assert_eq!(Date::parse("15/07/1990"), None);
+        assert_eq!(Date::parse(""), None);
+    }
+
+    #[test]
+    fn converts_ic_time_to_utc_dates() {
+        const NANOS_PER_SEC: u64 = 1_000_000_000;
+        assert_eq!(Date::from_nanos(0), date(1970, 1, 1));
+        assert_eq!(Date::from_nanos(951_782_400 * NANOS_PER_SEC), date(2000, 2, 29));
+        assert_eq!(Date::from_nanos(1_700_000_000 * NANOS_PER_SEC), date(2023, 11, 14));
+        // One nanosecond before midnight is still the previous day.
+        assert_eq!(Date::from_nanos(NANOS_PER_DAY - 1), date(1970, 1, 1));
+        assert_eq!(Date::from_nanos(NANOS_PER_DAY), date(1970, 1, 2));
+    }
+
+    #[test]
+    fn counts_completed_years() {
+        let dob = date(2000, 6, 15);
+        assert_eq!(dob.age_on(date(2018, 6, 14)), Some(17));
+        assert_eq!(dob.age_on(date(2018, 6, 15)), Some(18));
+        assert_eq!(dob.age_on(date(2018, 12, 31)), Some(18));
+        assert_eq!(dob.age_on(dob), Some(0));
+        assert_eq!(dob.age_on(date(2000, 6, 14)), None);
+    }
+
+    #[test]
+    fn leap_day_birthdays_count_from_march_first() {
+        let dob = date(2000, 2, 29);
+        assert_eq!(dob.age_on(date(2001, 2, 28)), Some(0));
+        assert_eq!(dob.age_on(date(2001, 3, 1)), Some(1));
+        assert_eq!(dob.age_on(date(2004, 2, 29)), Some(4));
+    }
+
+    #[test]
+    fn derives_age_from_stored_dob() {
+        let today = date(2024, 1, 1);
+        assert_eq!(age_from_dob(&Some("1990-01-01".to_string()), today), Some(34));
+        assert_eq!(age_from_dob(&Some("2030-01-01".to_string()), today), None);
+        assert_eq!(age_from_dob(&Some("not a date".to_string()), today), None);
+        assert_eq!(age_from_dob(&None, today), None);
+    }
+}
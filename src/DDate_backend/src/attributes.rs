@@ -0,0 +1,260 @@
+use candid::CandidType;
+use serde::{Deserialize, Serialize};
+
+// A categorical profile attribute stored as a candid variant.
+pub trait Choice: Sized {
+    // Maps free text onto a variant. Case, spaces and punctuation are ignored, and unknown text
+    // becomes `Other` on types that have it.
+    fn from_text(value: &str) -> Option<Self>;
+
+    fn label(&self) -> String;
+
+    // The text carried by an `Other` variant.
+    fn other_text(&self) -> Option<&str> {
+        None
+    }
+
+    // Turns `Other("male")` into `Male` so that equal values compare equal.
+    fn normalized(self) -> Self {
+        match self.other_text().and_then(Self::from_text) {
+            Some(known) => known,
+            None => self,
+        }
+    }
+}
+
+fn normalize(value: &str) -> String {
+    value.chars().filter(|c| c.is_alphanumeric()).flat_map(char::to_lowercase).collect()
+}
+
+// Declares a variant type whose labels match the options offered by the frontend. `open` types
+// also get an `Other(text)` case for values outside the list.
+macro_rules! choice {
+    ($(#[$meta:meta])* $name:ident { $($variant:ident => $label:literal),+ $(,)? }) => {
+        $(#[$meta])*
+        #[derive(Debug, Serialize, Clone, Deserialize, CandidType, PartialEq, Eq)]
+        pub enum $name {
+            $($variant),+
+        }
+
+        impl Choice for $name {
+            fn from_text(value: &str) -> Option<Self> {
+                let value = normalize(value);
+                $(if value == normalize($label) {
+                    return Some($name::$variant);
+                })+
+                None
+            }
+
+            fn label(&self) -> String {
+                match self {
+                    $($name::$variant => $label.to_string()),+
+                }
+            }
+        }
+    };
+    ($(#[$meta:meta])* open $name:ident { $($variant:ident => $label:literal),+ $(,)? }) => {
+        $(#[$meta])*
+        #[derive(Debug, Serialize, Clone, Deserialize, CandidType, PartialEq, Eq)]
+        pub enum $name {
+            $($variant,)+
+            Other(String),
+        }
+
+        impl Choice for $name {
+            fn from_text(value: &str) -> Option<Self> {
+                let normalized = normalize(value);
+                $(if normalized == normalize($label) {
+                    return Some($name::$variant);
+                })+
+                let value = value.trim();
+                if value.is_empty() {
+                    None
+                } else {
+                    Some($name::Other(value.to_string()))
+                }
+            }
+
+            fn label(&self) -> String {
+                match self {
+                    $($name::$variant => $label.to_string(),)+
+                    $name::Other(text) => text.clone(),
+                }
+            }
+
+            fn other_text(&self) -> Option<&str> {
+                match self {
+                    $name::Other(text) => Some(text),
+                    _ => None,
+                }
+            }
+        }
+    };
+}
+
+choice!(
+    // Also used for `interests_in`, which names the gender a user wants to be shown.
+    open Gender {
+        Male => "Male",
+        Female => "Female",
+        NonBinary => "Non binary",
+    }
+);
+
+choice!(
+    open GenderPronouns {
+        HeHim => "He/Him",
+        SheHer => "She/Her",
+        TheyThem => "They/Them",
+    }
+);
+
+choice!(
+    open Religion {
+        Hindu => "Hindu",
+        Muslim => "Muslim",
+        Sikh => "Sikh",
+        Christian => "Christian",
+        Jain => "Jain",
+        Catholic => "Catholic",
+        Agnostic => "Agnostic",
+        Jewish => "Jewish",
+        Atheist => "Atheist",
+        Buddhist => "Buddhist",
+        Spiritual => "Spiritual",
+    }
+);
+
+choice!(
+    open Diet {
+        Vegan => "Vegan",
+        Vegetarian => "Vegetarian",
+        Omnivore => "Omnivore",
+        Kosher => "Kosher",
+        Carnivore => "Carnivore",
+        Halal => "Halal",
+        Pescatarian => "Pescatarian",
+    }
+);
+
+choice!(
+    // Animal signs of the lunar zodiac.
+    Zodiac {
+        Rat => "Rat",
+        Ox => "Ox",
+        Tiger => "Tiger",
+        Cat => "Cat",
+        Dragon => "Dragon",
+        Snake => "Snake",
+        Horse => "Horse",
+        Goat => "Goat",
+        Monkey => "Monkey",
+        Rooster => "Rooster",
+        Dog => "Dog",
+        Pig => "Pig",
+    }
+);
+
+choice!(
+    Smoking {
+        SocialSmoker => "Social smoker",
+        Smoker => "Smoker",
+        Vaper => "Vaper",
+        NonSmoker => "Non-smoker",
+    }
+);
+
+choice!(
+    Drinking {
+        Regular => "Regular",
+        Socially => "Socially",
+        SpecialOccasions => "Special Occasions",
+        Never => "Never",
+    }
+);
+
+// Free-form values written by the seed scripts before the options were fixed, with the label that replaced them.
+// Tried only when the text fits no variant directly, so `Drinking` keeps its own "Regular" and "Never".
+const LEGACY_LABELS: &[(&str, &str)] = &[
+    ("Regular", "Smoker"),
+    ("Sometimes", "Social smoker"),
+    ("Never", "Non-smoker"),
+];
+
+// Maps a stored free-form value onto `T`; text that fits no variant of a closed type is dropped.
+pub fn choice_from_legacy<T: Choice>(value: Option<String>) -> Option<T> {
+    let value = value?;
+    T::from_text(&value).or_else(|| {
+        LEGACY_LABELS
+            .iter()
+            .find(|(old, _)| normalize(old) == normalize(&value))
+            .and_then(|(_, label)| T::from_text(label))
+    })
+}
+
+// Whether `choice_from_legacy` would lose `value`: it holds text but maps to no variant.
+pub fn legacy_choice_dropped<T: Choice>(value: &Option<String>) -> bool {
+    value.as_ref().is_some_and(|text| !text.trim().is_empty() && choice_from_legacy::<T>(Some(text.clone())).is_none())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn from_text_ignores_case_spaces_and_punctuation() {
+        assert_eq!(Gender::from_text("non-binary"), Some(Gender::NonBinary));
+        assert_eq!(Gender::from_text("  MALE "), Some(Gender::Male));
+        assert_eq!(GenderPronouns::from_text("they them"), Some(GenderPronouns::TheyThem));
+        assert_eq!(Smoking::from_text("NON SMOKER"), Some(Smoking::NonSmoker));
+        assert_eq!(Drinking::from_text("special_occasions"), Some(Drinking::SpecialOccasions));
+    }
+
+    #[test]
+    fn open_types_keep_unknown_text() {
+        assert_eq!(Religion::from_text(" Pastafarian "), Some(Religion::Other("Pastafarian".to_string())));
+        assert_eq!(Diet::from_text("Keto").map(|diet| diet.label()), Some("Keto".to_string()));
+        assert_eq!(Gender::from_text("   "), None);
+    }
+
+    #[test]
+    fn closed_types_reject_unknown_text() {
+        assert_eq!(Zodiac::from_text("Aquarius"), None);
+        assert_eq!(Smoking::from_text(""), None);
+    }
+
+    #[test]
+    fn normalized_resolves_other_to_a_known_variant() {
+        assert_eq!(Gender::Other("female".to_string()).normalized(), Gender::Female);
+        assert_eq!(Gender::Other("Agender".to_string()).normalized(), Gender::Other("Agender".to_string()));
+        assert_eq!(Gender::Male.normalized(), Gender::Male);
+    }
+
+    #[test]
+    fn labels_round_trip_through_from_text() {
+        for zodiac in [Zodiac::Rat, Zodiac::Dragon, Zodiac::Pig] {
+            assert_eq!(Zodiac::from_text(&zodiac.label()), Some(zodiac));
+        }
+        assert_eq!(Smoking::from_text(&Smoking::SocialSmoker.label()), Some(Smoking::SocialSmoker));
+    }
+
+    #[test]
+    fn legacy_labels_apply_only_when_no_variant_matches() {
+        assert_eq!(choice_from_legacy::<Smoking>(Some("Regular".to_string())), Some(Smoking::Smoker));
+        assert_eq!(choice_from_legacy::<Smoking>(Some("sometimes".to_string())), Some(Smoking::SocialSmoker));
+        assert_eq!(choice_from_legacy::<Smoking>(Some("Never".to_string())), Some(Smoking::NonSmoker));
+        assert_eq!(choice_from_legacy::<Drinking>(Some("Regular".to_string())), Some(Drinking::Regular));
+        assert_eq!(choice_from_legacy::<Drinking>(Some("Never".to_string())), Some(Drinking::Never));
+        assert_eq!(choice_from_legacy::<Zodiac>(None), None);
+    }
+
+    #[test]
+    fn reports_legacy_values_that_would_be_dropped() {
+        assert!(legacy_choice_dropped::<Zodiac>(&Some("Aquarius".to_string())));
+        assert!(!legacy_choice_dropped::<Zodiac>(&Some("Dragon".to_string())));
+        assert!(!legacy_choice_dropped::<Smoking>(&Some("Sometimes".to_string())));
+        assert!(!legacy_choice_dropped::<Religion>(&Some("Pastafarian".to_string())));
+        assert!(!legacy_choice_dropped::<Zodiac>(&Some(" ".to_string())));
+        assert!(!legacy_choice_dropped::<Zodiac>(&None));
+    }
+}
@@ -0,0 +1,312 @@
+use std::borrow::Cow;
+use std::time::Duration;
+
+use candid::{CandidType, Principal};
+use hmac::{Hmac, Mac};
+use ic_cdk::api::management_canister::main::raw_rand;
+use ic_cdk::query;
+use ic_stable_structures::storable::Bound;
+use ic_stable_structures::Storable;
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+
+use crate::attributes::Choice;
+use crate::errors::DDateError;
+use crate::guards::{caller_user_id, is_admin, is_anonymous};
+use crate::profile_creation::{Pagination, UserProfileCreationInfo};
+use crate::state_handler::{mutate_state, read_state, Candid, State};
+
+// Fields whose values never appear in the log in clear text.
+const SENSITIVE_FIELDS: &[&str] = &["email", "mobile_number", "dob"];
+
+// Position of one entry in the log: a user's entries sort together, oldest first.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct AuditKey {
+    pub user_id: String,
+    pub seq: u64,
+}
+
+impl AuditKey {
+    fn last_of(user_id: &str) -> Self {
+        AuditKey { user_id: user_id.to_string(), seq: u64::MAX }
+    }
+}
+
+// The user id followed by the big-endian sequence number.
+impl Storable for AuditKey {
+    const BOUND: Bound = Bound::Unbounded;
+
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        let mut bytes = self.user_id.as_bytes().to_vec();
+        bytes.extend_from_slice(&self.seq.to_be_bytes());
+        Cow::Owned(bytes)
+    }
+
+    fn from_bytes(bytes: Cow<'_, [u8]>) -> Self {
+        let (user_id, seq) = bytes.split_at(bytes.len() - 8);
+        AuditKey {
+            user_id: String::from_utf8(user_id.to_vec()).expect("audit keys hold valid user ids"),
+            seq: u64::from_be_bytes(seq.try_into().expect("split off eight bytes")),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Clone, Deserialize, CandidType)]
+pub struct FieldChange {
+    pub field: String,
+    pub old_value: Option<String>,
+    pub new_value: Option<String>,
+    // Set for sensitive fields, whose values are hex HMAC-SHA256 digests under a canister-held key
+    // instead of the stored text. They are left out while that key has not been generated yet.
+    pub hashed: bool,
+}
+
+#[derive(Debug, Serialize, Clone, Deserialize, CandidType)]
+pub struct AuditEntry {
+    pub changed_at: u64,
+    pub caller: Principal,
+    // Profile version written by this change.
+    pub version: u64,
+    pub changes: Vec<FieldChange>,
+}
+
+fn text(value: &Option<String>) -> Option<String> {
+    value.clone()
+}
+
+fn number(value: &Option<u64>) -> Option<String> {
+    value.map(|value| value.to_string())
+}
+
+fn choice<T: Choice>(value: &Option<T>) -> Option<String> {
+    value.as_ref().map(Choice::label)
+}
+
+fn list(value: &Option<Vec<String>>) -> Option<String> {
+    value.as_ref().map(|items| items.join(", "))
+}
+
+// The user-facing parts of a profile, rendered as text so two versions can be compared field by field.
+// Swipes, matches and notifications are left out; they change constantly and have their own endpoints.
+fn audited_fields(profile: &UserProfileCreationInfo) -> Vec<(&'static str, Option<String>)> {
+    let params = &profile.params;
+    vec![
+        ("gender", choice(&params.gender)),
+        ("email", text(&params.email)),
+        ("name", text(&params.name)),
+        ("mobile_number", text(&params.mobile_number)),
+        ("dob", text(&params.dob)),
+        ("gender_pronouns", choice(&params.gender_pronouns)),
+        ("life_path_number", text(&params.life_path_number)),
+        ("religion", choice(&params.religion)),
+        ("zodiac", choice(&params.zodiac)),
+        ("diet", choice(&params.diet)),
+        ("smoking", choice(&params.smoking)),
+        ("drinking", choice(&params.drinking)),
+        ("hobbies", list(&params.hobbies)),
+        ("sports", list(&params.sports)),
+        ("interests_in", choice(&params.interests_in)),
+        ("min_preferred_age", number(&params.min_preferred_age)),
+        ("max_preferred_age", number(&params.max_preferred_age)),
+        ("location_city", text(&params.location_city)),
+        ("location_state", text(&params.location_state)),
+        ("location_country", text(&params.location_country)),
+        ("preferred_city", text(&params.preferred_city)),
+        ("preferred_state", text(&params.preferred_state)),
+        ("preferred_country", text(&params.preferred_country)),
+        ("introduction", text(&params.introduction)),
+        ("images", list(&params.images)),
+        ("age", number(&params.age)),
+        ("privacy", params.privacy.as_ref().map(|privacy| format!("{:?}", privacy))),
+        ("extended", params.extended.as_ref().map(|extended| format!("{:?}", extended))),
+        ("account_state", Some(format!("{:?}", profile.account_state))),
+        ("paused_until", number(&profile.paused_until)),
+        ("deletion_scheduled_at", number(&profile.deletion_scheduled_at)),
+    ]
+}
+
+// Mixing in the user id keeps equal values of two users from producing the same digest.
+fn hash_value(key: Option<&[u8]>, user_id: &str, value: Option<String>) -> Option<String> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key?).expect("HMAC accepts keys of any length");
+    mac.update(user_id.as_bytes());
+    mac.update(&[0]);
+    mac.update(value?.as_bytes());
+    Some(format!("{:x}", mac.finalize().into_bytes()))
+}
+
+// Lists every audited field that differs between `old` and `new`; a missing `old` means the profile was just created.
+// `key` is the canister's audit key, see `State::audit_hash_key`.
+pub fn diff_profiles(old: Option<&UserProfileCreationInfo>, new: &UserProfileCreationInfo, key: Option<&[u8]>) -> Vec<FieldChange> {
+    let old_fields = old.map(audited_fields);
+    audited_fields(new)
+        .into_iter()
+        .enumerate()
+        .filter_map(|(i, (field, new_value))| {
+            let old_value = old_fields.as_ref().and_then(|fields| fields[i].1.clone());
+            if old_value == new_value {
+                return None;
+            }
+            let hashed = SENSITIVE_FIELDS.contains(&field);
+            let (old_value, new_value) = if hashed {
+                (hash_value(key, &new.user_id, old_value), hash_value(key, &new.user_id, new_value))
+            } else {
+                (old_value, new_value)
+            };
+            Some(FieldChange {
+                field: field.to_string(),
+                old_value,
+                new_value,
+                hashed,
+            })
+        })
+        .collect()
+}
+
+impl State {
+    // None until `start_audit_key_timer` has stored the key.
+    pub fn audit_hash_key(&self) -> Option<Vec<u8>> {
+        let key = &self.audit_key.get().0;
+        (!key.is_empty()).then(|| key.clone())
+    }
+
+    fn set_audit_hash_key(&mut self, key: Vec<u8>) {
+        if self.audit_hash_key().is_none() {
+            self.audit_key
+                .set(Candid(key))
+                .unwrap_or_else(|e| ic_cdk::trap(&format!("Failed to store audit key: {:?}", e)));
+        }
+    }
+
+    // Entries are only ever appended. The only removal is `remove_profile_history`, when the account is deleted.
+    pub fn record_profile_change(&mut self, user_id: &str, entry: AuditEntry) {
+        let seq = self
+            .audit_log
+            .iter_upper_bound(&AuditKey::last_of(user_id))
+            .next()
+            .filter(|(key, _)| key.user_id == user_id)
+            .map_or(0, |(key, _)| key.seq + 1);
+        self.audit_log.insert(AuditKey { user_id: user_id.to_string(), seq }, Candid(entry));
+    }
+
+    fn history_range(&self, user_id: &str) -> impl Iterator<Item = (AuditKey, Candid<AuditEntry>)> + '_ {
+        let first = AuditKey { user_id: user_id.to_string(), seq: 0 };
+        self.audit_log.range(first..=AuditKey::last_of(user_id))
+    }
+
+    pub fn has_profile_history(&self, user_id: &str) -> bool {
+        self.history_range(user_id).next().is_some()
+    }
+
+    // Oldest first.
+    pub fn profile_history(&self, user_id: &str, pagination: &Pagination) -> Vec<AuditEntry> {
+        self.history_range(user_id)
+            .skip((pagination.page - 1) * pagination.size)
+            .take(pagination.size)
+            .map(|(_, entry)| entry.0)
+            .collect()
+    }
+
+    // Deleting an account erases its history along with the rest of its data; no tombstone is kept.
+    pub fn remove_profile_history(&mut self, user_id: &str) {
+        let keys: Vec<AuditKey> = self.history_range(user_id).map(|(key, _)| key).collect();
+        for key in keys {
+            self.audit_log.remove(&key);
+        }
+    }
+
+    // Moves entries kept as one blob per user into the per-entry log. Sensitive values in those entries
+    // were hashed without a key, so they are dropped rather than carried over. Returns the entries moved.
+    pub fn migrate_legacy_audit_log(&mut self) -> u64 {
+        let mut moved = 0;
+        let legacy: Vec<_> = self.legacy_audit_log.iter().collect();
+        for (user_id, log) in legacy {
+            for mut entry in log.0 {
+                for change in entry.changes.iter_mut().filter(|change| change.hashed) {
+                    change.old_value = None;
+                    change.new_value = None;
+                }
+                self.record_profile_change(&user_id, entry);
+                moved += 1;
+            }
+        }
+        self.legacy_audit_log.clear_new();
+        moved
+    }
+}
+
+async fn generate_audit_key() {
+    match raw_rand().await {
+        Ok((key,)) => mutate_state(|state| state.set_audit_hash_key(key)),
+        Err(e) => ic_cdk::println!("Failed to generate the audit key: {:?}", e),
+    }
+}
+
+// `raw_rand` cannot be awaited in `init` or `post_upgrade`, so the key is made by a timer right after.
+pub fn start_audit_key_timer() {
+    if read_state(|state| state.audit_hash_key().is_none()) {
+        ic_cdk_timers::set_timer(Duration::ZERO, || ic_cdk::spawn(generate_audit_key()));
+    }
+}
+
+#[query(guard = "is_anonymous")]
+pub fn get_my_profile_history(pagination: Pagination) -> Result<Vec<AuditEntry>, DDateError> {
+    read_state(|state| {
+        let user_id = caller_user_id(state)?;
+        state.validate_pagination(&pagination)?;
+        Ok(state.profile_history(&user_id, &pagination))
+    })
+}
+
+#[query(guard = "is_admin")]
+pub fn get_profile_history(user_id: String, pagination: Pagination) -> Result<Vec<AuditEntry>, DDateError> {
+    read_state(|state| {
+        if !state.user_profiles.contains_key(&user_id) && !state.has_profile_history(&user_id) {
+            return Err(DDateError::NotFound(format!("No profile history for user_id: {}", user_id)));
+        }
+        state.validate_pagination(&pagination)?;
+        Ok(state.profile_history(&user_id, &pagination))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ic_stable_structures::{DefaultMemoryImpl, StableBTreeMap};
+
+    fn key(user_id: &str, seq: u64) -> AuditKey {
+        AuditKey { user_id: user_id.to_string(), seq }
+    }
+
+    #[test]
+    fn keys_sort_by_user_then_numeric_seq() {
+        let mut keys = vec![key("u2", 0), key("u1", 10), key("u10", 0), key("u1", 2), key("u1", 0)];
+        keys.sort();
+        assert_eq!(keys, vec![key("u1", 0), key("u1", 2), key("u1", 10), key("u10", 0), key("u2", 0)]);
+        assert!(key("u1", u64::MAX) < key("u10", 0));
+    }
+
+    #[test]
+    fn keys_round_trip_through_bytes() {
+        for original in [key("u1", 0), key("user-42", 257), key("", u64::MAX)] {
+            assert_eq!(AuditKey::from_bytes(original.to_bytes()), original);
+        }
+        assert_eq!(key("u1", 1).to_bytes().as_ref(), b"u1\0\0\0\0\0\0\0\x01");
+    }
+
+    #[test]
+    fn a_user_range_holds_only_that_users_entries() {
+        let mut log: StableBTreeMap<AuditKey, u64, _> = StableBTreeMap::new(DefaultMemoryImpl::default());
+        for (user_id, seq) in [("u1", 0), ("u1", 1), ("u1", 10), ("u10", 0), ("u", 5), ("u2", 3)] {
+            log.insert(key(user_id, seq), seq);
+        }
+
+        let u1: Vec<u64> = log.range(key("u1", 0)..=AuditKey::last_of("u1")).map(|(key, _)| key.seq).collect();
+        assert_eq!(u1, vec![0, 1, 10]);
+
+        let newest = log.iter_upper_bound(&AuditKey::last_of("u1")).next().map(|(key, _)| key);
+        assert_eq!(newest, Some(key("u1", 10)));
+        // A user without entries lands on a neighbour's key, which is why callers check the user id.
+        let neighbour = log.iter_upper_bound(&AuditKey::last_of("u0")).next().map(|(key, _)| key);
+        assert_eq!(neighbour, Some(key("u", 5)));
+    }
+}
@@ -0,0 +1,201 @@
+use std::cell::Cell;
+use std::ops::Bound;
+use std::time::Duration;
+
+use candid::CandidType;
+use ic_cdk::query;
+use serde::{Deserialize, Serialize};
+
+use crate::guards::is_admin;
+use crate::profile_creation::UserProfileCreationInfo;
+use crate::state_handler::{mutate_state, read_state, Candid, State};
+
+// Instructions a single tick may spend before it hands the rest of the work to the next tick; well below
+// the per-message limit so a tick never traps halfway through a record.
+const TICK_INSTRUCTION_BUDGET: u64 = 2_000_000_000;
+
+thread_local! {
+    static TICK_SCHEDULED: Cell<bool> = const { Cell::new(false) };
+}
+
+#[derive(Debug, Serialize, Clone, Copy, Deserialize, CandidType, PartialEq, Eq)]
+pub enum DeletionPhase {
+    // Removing the user's id from other profiles' swipes, likes, matches and notifications.
+    Profiles,
+    // Removing every chat the user took part in.
+    Messages,
+}
+
+// A cascading delete in progress. The account's own records are removed when the job is queued; the job
+// only has to scrub what other records still say about it.
+#[derive(Debug, Serialize, Clone, Deserialize, CandidType)]
+pub struct DeletionJob {
+    pub user_id: String,
+    pub started_at: u64,
+    pub phase: DeletionPhase,
+    // Last key handled in the current phase; the next tick resumes after it.
+    pub cursor: Option<String>,
+    pub profiles_updated: u64,
+    pub chats_removed: u64,
+}
+
+pub enum Progress {
+    Done,
+    OutOfBudget,
+}
+
+pub fn over_budget() -> bool {
+    ic_cdk::api::instruction_counter() > TICK_INSTRUCTION_BUDGET
+}
+
+pub fn after(cursor: &Option<String>) -> (Bound<String>, Bound<String>) {
+    match cursor {
+        Some(cursor) => (Bound::Excluded(cursor.clone()), Bound::Unbounded),
+        None => (Bound::Unbounded, Bound::Unbounded),
+    }
+}
+
+// Chat ids are the two user ids joined by `_`, see `State::get_chat_id`.
+pub fn chat_includes(chat_id: &str, user_id: &str) -> bool {
+    chat_id.split('_').any(|part| part == user_id)
+}
+
+// Drops every mention of `user_id` from `profile`; returns whether anything changed.
+fn scrub_profile(profile: &mut UserProfileCreationInfo, user_id: &str) -> bool {
+    let mut changed = false;
+    let params = &mut profile.params;
+    for set in [&mut params.likes, &mut params.leftswipes, &mut params.rightswipes].into_iter().flatten() {
+        changed |= set.remove(user_id);
+    }
+    for list in [&mut params.matches, &mut params.matched_profiles].into_iter().flatten() {
+        let before = list.len();
+        list.retain(|id| id != user_id);
+        changed |= list.len() != before;
+    }
+    for notifications in [params.notifications.as_mut(), Some(&mut profile.notifications)].into_iter().flatten() {
+        let before = notifications.len();
+        notifications.retain(|notification| notification.sender_id != user_id && notification.receiver_id != user_id);
+        changed |= notifications.len() != before;
+    }
+    let before = profile.matched_profiles.len();
+    profile.matched_profiles.retain(|id| id != user_id);
+    changed |= profile.matched_profiles.len() != before;
+    changed
+}
+
+impl State {
+    // Removes the account, its profile history and its exports right away, and queues the job that cleans
+    // up every reference to it.
+    pub fn start_cascading_delete(&mut self, user_id: &str, now: u64) {
+        self.remove_account_records(user_id);
+        self.remove_profile_history(user_id);
+        self.remove_data_exports(user_id);
+        let job = DeletionJob {
+            user_id: user_id.to_string(),
+            started_at: now,
+            phase: DeletionPhase::Profiles,
+            cursor: None,
+            profiles_updated: 0,
+            chats_removed: 0,
+        };
+        self.deletion_jobs.insert(user_id.to_string(), Candid(job));
+    }
+
+    fn scrub_profiles(&mut self, job: &mut DeletionJob) -> Progress {
+        loop {
+            let next = self.user_profiles.range(after(&job.cursor)).next();
+            let Some((id, mut profile)) = next else {
+                return Progress::Done;
+            };
+            if scrub_profile(&mut profile, &job.user_id) {
+                self.save_profile(id.clone(), profile);
+                job.profiles_updated += 1;
+            }
+            job.cursor = Some(id);
+            if over_budget() {
+                return Progress::OutOfBudget;
+            }
+        }
+    }
+
+    fn remove_chats(&mut self, job: &mut DeletionJob) -> Progress {
+        loop {
+            let next = self.user_messages.range(after(&job.cursor)).map(|(chat_id, _)| chat_id).next();
+            let Some(chat_id) = next else {
+                return Progress::Done;
+            };
+            if chat_includes(&chat_id, &job.user_id) {
+                self.user_messages.remove(&chat_id);
+                job.chats_removed += 1;
+            }
+            job.cursor = Some(chat_id);
+            if over_budget() {
+                return Progress::OutOfBudget;
+            }
+        }
+    }
+
+    // Advances the queued jobs until they are finished or the tick's budget runs out. Returns whether
+    // work is left for another tick.
+    pub fn run_deletion_jobs(&mut self) -> bool {
+        let queued: Vec<String> = self.deletion_jobs.iter().map(|(user_id, _)| user_id).collect();
+        for user_id in queued {
+            let Some(Candid(mut job)) = self.deletion_jobs.get(&user_id) else {
+                continue;
+            };
+            loop {
+                let progress = match job.phase {
+                    DeletionPhase::Profiles => self.scrub_profiles(&mut job),
+                    DeletionPhase::Messages => self.remove_chats(&mut job),
+                };
+                match (progress, job.phase) {
+                    (Progress::OutOfBudget, _) => {
+                        self.deletion_jobs.insert(user_id, Candid(job));
+                        return true;
+                    }
+                    (Progress::Done, DeletionPhase::Profiles) => {
+                        job.phase = DeletionPhase::Messages;
+                        job.cursor = None;
+                    }
+                    (Progress::Done, DeletionPhase::Messages) => {
+                        ic_cdk::println!(
+                            "Finished deleting user ID {}: {} profiles updated, {} chats removed",
+                            job.user_id, job.profiles_updated, job.chats_removed
+                        );
+                        self.deletion_jobs.remove(&user_id);
+                        break;
+                    }
+                }
+            }
+        }
+        false
+    }
+}
+
+fn run_deletion_tick() {
+    TICK_SCHEDULED.with(|scheduled| scheduled.set(false));
+    if mutate_state(|state| state.run_deletion_jobs()) {
+        schedule_deletion_tick();
+    }
+}
+
+// Queues a tick unless one is already pending. Each tick is its own message, so its instruction count
+// starts from zero.
+pub fn schedule_deletion_tick() {
+    if TICK_SCHEDULED.with(|scheduled| scheduled.replace(true)) {
+        return;
+    }
+    ic_cdk_timers::set_timer(Duration::ZERO, run_deletion_tick);
+}
+
+// Picks up jobs that were interrupted by an upgrade.
+pub fn start_deletion_timer() {
+    if read_state(|state| !state.deletion_jobs.is_empty()) {
+        schedule_deletion_tick();
+    }
+}
+
+#[query(guard = "is_admin")]
+pub fn get_deletion_jobs() -> Vec<DeletionJob> {
+    read_state(|state| state.deletion_jobs.iter().map(|(_, job)| job.0).collect())
+}
@@ -0,0 +1,171 @@
+use candid::CandidType;
+use ic_cdk::query;
+use serde::{Deserialize, Serialize};
+
+use crate::errors::DDateError;
+use crate::guards::{authorize_user, is_anonymous};
+use crate::profile_creation::UserProfileParams;
+use crate::state_handler::read_state;
+
+const RECOMMENDED_IMAGES: usize = 3;
+const RECOMMENDED_INTRODUCTION_LENGTH: usize = 50;
+const RECOMMENDED_EXTENDED_FIELDS: usize = 3;
+
+// How discovery treats profiles scoring below `CanisterConfig::completeness_threshold`.
+#[derive(Debug, Serialize, Clone, Copy, Deserialize, CandidType, PartialEq, Eq)]
+pub enum IncompleteProfiles {
+    // Shown after every complete profile.
+    DownRank,
+    Hide,
+}
+
+#[derive(Debug, Serialize, Clone, Copy, Deserialize, CandidType, PartialEq, Eq)]
+pub enum GapKind {
+    Missing,
+    // Filled in, but not enough to earn the section's full weight.
+    Weak,
+}
+
+#[derive(Debug, Serialize, Clone, Deserialize, CandidType)]
+pub struct SectionGap {
+    pub section: String,
+    pub kind: GapKind,
+    pub message: String,
+    // Points the score would gain if the section were completed.
+    pub points: u64,
+}
+
+#[derive(Debug, Serialize, Clone, Deserialize, CandidType)]
+pub struct CompletenessReport {
+    // 0 to 100.
+    pub score: u64,
+    pub gaps: Vec<SectionGap>,
+}
+
+#[derive(Default)]
+struct Scorer {
+    score: u64,
+    gaps: Vec<SectionGap>,
+}
+
+impl Scorer {
+    // Full weight when complete, half when weak, nothing when missing.
+    fn section(&mut self, section: &str, weight: u64, kind: Option<GapKind>, message: &str) {
+        let earned = match kind {
+            None => weight,
+            Some(GapKind::Weak) => weight / 2,
+            Some(GapKind::Missing) => 0,
+        };
+        self.score += earned;
+        if let Some(kind) = kind {
+            self.gaps.push(SectionGap {
+                section: section.to_string(),
+                kind,
+                message: message.to_string(),
+                points: weight - earned,
+            });
+        }
+    }
+
+    fn present<T>(&mut self, section: &str, weight: u64, value: &Option<T>, message: &str) {
+        let kind = value.is_none().then_some(GapKind::Missing);
+        self.section(section, weight, kind, message);
+    }
+
+    fn list(&mut self, section: &str, weight: u64, value: &Option<Vec<String>>, message: &str) {
+        let kind = value.as_ref().is_none_or(|items| items.is_empty()).then_some(GapKind::Missing);
+        self.section(section, weight, kind, message);
+    }
+}
+
+// Weights add up to 100.
+pub fn completeness(params: &UserProfileParams) -> CompletenessReport {
+    let mut scorer = Scorer::default();
+
+    let images = params.images.as_ref().map_or(0, Vec::len);
+    let images_gap = match images {
+        0 => Some(GapKind::Missing),
+        n if n < RECOMMENDED_IMAGES => Some(GapKind::Weak),
+        _ => None,
+    };
+    scorer.section(
+        "images",
+        25,
+        images_gap,
+        &format!("Add at least {} photos", RECOMMENDED_IMAGES),
+    );
+
+    let introduction = params.introduction.as_deref().map_or(0, |intro| intro.trim().chars().count());
+    let introduction_gap = match introduction {
+        0 => Some(GapKind::Missing),
+        n if n < RECOMMENDED_INTRODUCTION_LENGTH => Some(GapKind::Weak),
+        _ => None,
+    };
+    scorer.section(
+        "introduction",
+        20,
+        introduction_gap,
+        &format!("Write an introduction of at least {} characters", RECOMMENDED_INTRODUCTION_LENGTH),
+    );
+
+    scorer.list("hobbies", 10, &params.hobbies, "Add some hobbies");
+    scorer.list("sports", 10, &params.sports, "Add the sports you play or follow");
+
+    let has_location = params.location_city.is_some() && params.location_country.is_some();
+    scorer.section(
+        "location",
+        10,
+        (!has_location).then_some(GapKind::Missing),
+        "Add your city and country",
+    );
+
+    scorer.present("gender_pronouns", 5, &params.gender_pronouns, "Choose your pronouns");
+    scorer.present("lifestyle", 5, &params.smoking.as_ref().and(params.drinking.as_ref()), "Tell others whether you smoke and drink");
+    scorer.present("diet", 5, &params.diet, "Add your diet");
+
+    let extended = params.extended.as_ref();
+    let extended_fields = extended.map_or(0, |extended| {
+        [
+            extended.height_cm.is_some(),
+            extended.occupation.is_some(),
+            extended.looking_for.is_some(),
+            extended.pets.is_some(),
+            extended.art_and_culture.is_some(),
+            extended.general_habits.is_some(),
+            extended.outdoor_activities.is_some(),
+            extended.travel.is_some(),
+            extended.movies.is_some(),
+        ]
+        .into_iter()
+        .filter(|filled| *filled)
+        .count()
+    });
+    let extended_gap = match extended_fields {
+        0 => Some(GapKind::Missing),
+        n if n < RECOMMENDED_EXTENDED_FIELDS => Some(GapKind::Weak),
+        _ => None,
+    };
+    scorer.section(
+        "extended",
+        10,
+        extended_gap,
+        &format!("Fill in at least {} of the extra sections", RECOMMENDED_EXTENDED_FIELDS),
+    );
+
+    CompletenessReport {
+        score: scorer.score,
+        gaps: scorer.gaps,
+    }
+}
+
+#[query(guard = "is_anonymous")]
+pub fn get_profile_completeness(user_id: String) -> Result<CompletenessReport, DDateError> {
+    read_state(|state| {
+        authorize_user(state, &user_id)?;
+        let profile = state
+            .user_profiles
+            .get(&user_id)
+            .ok_or_else(|| DDateError::NotFound("Profile not found".to_string()))?;
+        Ok(completeness(&profile.params))
+    })
+}
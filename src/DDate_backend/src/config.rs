@@ -0,0 +1,217 @@
+use candid::{CandidType, Principal};
+use ic_cdk::{caller, query, update};
+use serde::{Deserialize, Serialize};
+
+use crate::completeness::IncompleteProfiles;
+use crate::errors::DDateError;
+use crate::guards::is_controller;
+use crate::profile_creation::Pagination;
+use crate::roles::Role;
+use crate::state_handler::{mutate_state, read_state, Candid, State};
+
+// Runtime settings that controllers can tune without rebuilding the canister.
+#[derive(Debug, Serialize, Clone, Deserialize, CandidType)]
+pub struct CanisterConfig {
+    pub max_notifications: u64,
+    pub max_page_size: u64,
+    // Swipes (left or right) a user can make per UTC day; 0 disables the quota.
+    pub daily_swipe_limit: u64,
+    pub max_message_length: u64,
+    // Youngest age, derived from `dob`, allowed to hold a profile; `None` means `DEFAULT_MIN_AGE`.
+    pub min_age: Option<u64>,
+    // Profiles scoring below this in `get_profile_completeness` are handled as `incomplete_profiles` says in discovery.
+    // `None` turns the check off.
+    pub completeness_threshold: Option<u64>,
+    // `None` means `DownRank`.
+    pub incomplete_profiles: Option<IncompleteProfiles>,
+    // Days between `request_account_deletion` and the removal; `None` means `DEFAULT_DELETION_GRACE_DAYS`.
+    pub deletion_grace_days: Option<u64>,
+}
+
+const DEFAULT_MIN_AGE: u64 = 18;
+const DEFAULT_DELETION_GRACE_DAYS: u64 = 14;
+const NANOS_PER_DAY: u64 = 24 * 60 * 60 * 1_000_000_000;
+
+impl Default for CanisterConfig {
+    fn default() -> Self {
+        CanisterConfig {
+            max_notifications: 100,
+            max_page_size: 100,
+            daily_swipe_limit: 0,
+            max_message_length: 2000,
+            min_age: None,
+            completeness_threshold: None,
+            incomplete_profiles: None,
+            deletion_grace_days: None,
+        }
+    }
+}
+
+// Partial update of `CanisterConfig`; fields left as `None` keep their current value.
+#[derive(Debug, Serialize, Clone, Default, Deserialize, CandidType)]
+pub struct ConfigUpdate {
+    pub max_notifications: Option<u64>,
+    pub max_page_size: Option<u64>,
+    pub daily_swipe_limit: Option<u64>,
+    pub max_message_length: Option<u64>,
+    pub min_age: Option<u64>,
+    pub completeness_threshold: Option<u64>,
+    pub incomplete_profiles: Option<IncompleteProfiles>,
+    pub deletion_grace_days: Option<u64>,
+}
+
+#[derive(Debug, Serialize, Clone, Deserialize, CandidType)]
+pub struct InitArgs {
+    pub config: Option<CanisterConfig>,
+    pub admins: Vec<Principal>,
+}
+
+#[derive(Debug, Serialize, Clone, Deserialize, CandidType)]
+pub struct UpgradeArgs {
+    pub config: Option<ConfigUpdate>,
+    pub admins: Vec<Principal>,
+}
+
+#[derive(Debug, Serialize, Clone, Deserialize, CandidType)]
+pub enum CanisterArgs {
+    Init(InitArgs),
+    Upgrade(UpgradeArgs),
+}
+
+impl CanisterConfig {
+    pub fn min_age(&self) -> u64 {
+        self.min_age.unwrap_or(DEFAULT_MIN_AGE)
+    }
+
+    pub fn incomplete_profiles(&self) -> IncompleteProfiles {
+        self.incomplete_profiles.unwrap_or(IncompleteProfiles::DownRank)
+    }
+
+    pub fn deletion_grace_nanos(&self) -> u64 {
+        self.deletion_grace_days
+            .unwrap_or(DEFAULT_DELETION_GRACE_DAYS)
+            .saturating_mul(NANOS_PER_DAY)
+    }
+
+    fn validate(&self) -> Result<(), DDateError> {
+        if self.max_notifications == 0 {
+            return Err(DDateError::InvalidInput("max_notifications must be greater than 0".to_string()));
+        }
+        if self.max_page_size == 0 {
+            return Err(DDateError::InvalidInput("max_page_size must be greater than 0".to_string()));
+        }
+        if self.max_message_length == 0 {
+            return Err(DDateError::InvalidInput("max_message_length must be greater than 0".to_string()));
+        }
+        if self.min_age() == 0 {
+            return Err(DDateError::InvalidInput("min_age must be greater than 0".to_string()));
+        }
+        if self.completeness_threshold.is_some_and(|threshold| threshold > 100) {
+            return Err(DDateError::InvalidInput("completeness_threshold must not exceed 100".to_string()));
+        }
+        Ok(())
+    }
+
+    fn apply(&mut self, update: ConfigUpdate) {
+        if let Some(max_notifications) = update.max_notifications {
+            self.max_notifications = max_notifications;
+        }
+        if let Some(max_page_size) = update.max_page_size {
+            self.max_page_size = max_page_size;
+        }
+        if let Some(daily_swipe_limit) = update.daily_swipe_limit {
+            self.daily_swipe_limit = daily_swipe_limit;
+        }
+        if let Some(max_message_length) = update.max_message_length {
+            self.max_message_length = max_message_length;
+        }
+        if let Some(min_age) = update.min_age {
+            self.min_age = Some(min_age);
+        }
+        if let Some(completeness_threshold) = update.completeness_threshold {
+            self.completeness_threshold = Some(completeness_threshold);
+        }
+        if let Some(incomplete_profiles) = update.incomplete_profiles {
+            self.incomplete_profiles = Some(incomplete_profiles);
+        }
+        if let Some(deletion_grace_days) = update.deletion_grace_days {
+            self.deletion_grace_days = Some(deletion_grace_days);
+        }
+    }
+}
+
+impl State {
+    pub fn config(&self) -> CanisterConfig {
+        self.config.get().0.clone()
+    }
+
+    pub fn set_config(&mut self, config: CanisterConfig) -> Result<CanisterConfig, DDateError> {
+        config.validate()?;
+        self.config
+            .set(Candid(config.clone()))
+            .map_err(|e| DDateError::Internal(format!("Failed to store config: {:?}", e)))?;
+        Ok(config)
+    }
+
+    pub fn update_config(&mut self, update: ConfigUpdate) -> Result<CanisterConfig, DDateError> {
+        let mut config = self.config();
+        config.apply(update);
+        self.set_config(config)
+    }
+
+    // Applies the arguments passed to `init` or `post_upgrade`; `post_upgrade` refuses `Init` before getting here.
+    pub fn apply_canister_args(&mut self, args: CanisterArgs, installer: Principal, now: u64) -> Result<(), DDateError> {
+        let admins = match args {
+            CanisterArgs::Init(init_args) => {
+                self.set_config(init_args.config.unwrap_or_default())?;
+                init_args.admins
+            }
+            CanisterArgs::Upgrade(upgrade_args) => {
+                if let Some(update) = upgrade_args.config {
+                    self.update_config(update)?;
+                }
+                upgrade_args.admins
+            }
+        };
+        for admin in admins {
+            self.assign_role(admin, Role::Admin, installer, now)?;
+        }
+        Ok(())
+    }
+
+    pub fn validate_pagination(&self, pagination: &Pagination) -> Result<(), DDateError> {
+        if pagination.page == 0 {
+            return Err(DDateError::InvalidInput("Page number must be greater than 0".to_string()));
+        }
+        if pagination.size == 0 {
+            return Err(DDateError::InvalidInput("Page size must be greater than 0".to_string()));
+        }
+        let max_page_size = self.config().max_page_size;
+        if pagination.size as u64 > max_page_size {
+            return Err(DDateError::InvalidInput(format!("Page size must not exceed {}", max_page_size)));
+        }
+        Ok(())
+    }
+
+    pub fn validate_message_length(&self, content: &str) -> Result<(), DDateError> {
+        let max_message_length = self.config().max_message_length;
+        if content.chars().count() as u64 > max_message_length {
+            return Err(DDateError::InvalidInput(format!(
+                "Message content must not exceed {} characters",
+                max_message_length
+            )));
+        }
+        Ok(())
+    }
+}
+
+#[query]
+pub fn get_config() -> CanisterConfig {
+    read_state(|state| state.config())
+}
+
+#[update(guard = "is_controller")]
+pub fn update_config(update: ConfigUpdate) -> Result<CanisterConfig, DDateError> {
+    ic_cdk::println!("Controller {} updating config: {:?}", caller(), update);
+    mutate_state(|state| state.update_config(update))
+}
@@ -0,0 +1,349 @@
+use std::collections::{HashSet, VecDeque};
+use std::time::Duration;
+
+use candid::CandidType;
+use ic_cdk::api::management_canister::main::raw_rand;
+use ic_cdk::{caller, query, update};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::cascade::chat_includes;
+use crate::errors::DDateError;
+use crate::guards::{authorize_user, caller_user_id, is_anonymous};
+use crate::profile_creation::{Message, Notification, UserProfileCreationInfo};
+use crate::state_handler::{mutate_state, read_state, Candid, State};
+
+// Bumped whenever `ExportBundle` changes shape, so clients know how to read an export.
+pub const EXPORT_FORMAT_VERSION: u16 = 1;
+// Keeps every chunk well inside the 2 MiB reply limit.
+const EXPORT_CHUNK_SIZE: usize = 1024 * 1024;
+const EXPORT_TTL_NANOS: u64 = 24 * 60 * 60 * 1_000_000_000;
+const EXPORT_PURGE_INTERVAL: Duration = Duration::from_secs(60 * 60);
+const EXPORT_PATH_PREFIX: &str = "/exports/";
+
+#[derive(Debug, Serialize, Clone, Deserialize, CandidType)]
+pub struct ChatExport {
+    pub chat_id: String,
+    pub with_user_id: String,
+    pub messages: VecDeque<Message>,
+}
+
+// Everything the canister holds about one user. Swipes made by the user are inside `profile.params`.
+#[derive(Debug, Serialize, Clone, Deserialize, CandidType)]
+pub struct ExportBundle {
+    pub format_version: u16,
+    pub exported_at: u64,
+    pub user_id: String,
+    pub profile: UserProfileCreationInfo,
+    // Users who swiped or liked this user.
+    pub received_rightswipes: Vec<String>,
+    pub received_leftswipes: Vec<String>,
+    pub received_likes: Vec<String>,
+    pub matches: Vec<String>,
+    pub notifications: Vec<Notification>,
+    pub chats: Vec<ChatExport>,
+}
+
+// A stored export: the candid-encoded `ExportBundle`, served in chunks.
+#[derive(Debug, Serialize, Clone, Deserialize, CandidType)]
+pub struct DataExport {
+    pub user_id: String,
+    pub created_at: u64,
+    pub format_version: u16,
+    pub bundle: Vec<u8>,
+    // SHA-256 of the one-time token for the JSON download; cleared once it is used.
+    pub json_token_hash: Option<Vec<u8>>,
+}
+
+#[derive(Debug, Serialize, Clone, Deserialize, CandidType)]
+pub struct ExportManifest {
+    pub export_id: String,
+    pub format_version: u16,
+    pub created_at: u64,
+    pub expires_at: u64,
+    pub total_bytes: u64,
+    pub chunk_size: u64,
+    pub chunk_count: u64,
+    // Hex SHA-256 of the concatenated chunks.
+    pub sha256: String,
+    // Path of the JSON rendering, including the one-time token; only returned here.
+    pub json_path: String,
+}
+
+#[derive(Debug, Clone, Deserialize, CandidType)]
+pub struct HttpRequest {
+    pub method: String,
+    pub url: String,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+#[derive(Debug, Clone, Serialize, CandidType)]
+pub struct HttpResponse {
+    pub status_code: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+    // Asks the HTTP gateway to repeat the request as `http_request_update`.
+    pub upgrade: Option<bool>,
+}
+
+impl HttpResponse {
+    fn text(status_code: u16, body: &str) -> Self {
+        HttpResponse {
+            status_code,
+            headers: vec![("Content-Type".to_string(), "text/plain; charset=utf-8".to_string())],
+            body: body.as_bytes().to_vec(),
+            upgrade: None,
+        }
+    }
+
+    fn upgrade() -> Self {
+        HttpResponse {
+            status_code: 200,
+            headers: Vec::new(),
+            body: Vec::new(),
+            upgrade: Some(true),
+        }
+    }
+}
+
+fn hash_token(token: &str) -> Vec<u8> {
+    Sha256::digest(token.as_bytes()).to_vec()
+}
+
+// Splits `/exports/<export_id>?token=<token>` into its export id and token.
+fn parse_export_url(url: &str) -> Option<(&str, &str)> {
+    let (path, query) = url.split_once('?')?;
+    let export_id = path.strip_prefix(EXPORT_PATH_PREFIX)?;
+    let token = query.split('&').find_map(|pair| pair.strip_prefix("token="))?;
+    Some((export_id, token))
+}
+
+fn hex_string(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+impl State {
+    pub fn build_export_bundle(&self, user_id: &str, now: u64) -> Result<ExportBundle, DDateError> {
+        let profile = self
+            .user_profiles
+            .get(&user_id.to_string())
+            .ok_or_else(|| DDateError::NotFound(format!("User ID '{}' not found", user_id)))?;
+
+        let mut received_rightswipes = Vec::new();
+        let mut received_leftswipes = Vec::new();
+        let mut received_likes = Vec::new();
+        for (id, other) in self.user_profiles.iter() {
+            let contains = |ids: &Option<HashSet<String>>| ids.as_ref().is_some_and(|ids| ids.contains(user_id));
+            if contains(&other.params.rightswipes) {
+                received_rightswipes.push(id.clone());
+            }
+            if contains(&other.params.leftswipes) {
+                received_leftswipes.push(id.clone());
+            }
+            if contains(&other.params.likes) {
+                received_likes.push(id);
+            }
+        }
+
+        let chats = self
+            .user_messages
+            .iter()
+            .filter(|(chat_id, _)| chat_includes(chat_id, user_id))
+            .map(|(chat_id, messages)| ChatExport {
+                with_user_id: chat_id.split('_').find(|part| *part != user_id).unwrap_or(user_id).to_string(),
+                chat_id,
+                messages: messages.0,
+            })
+            .collect();
+
+        let mut matches = profile.matched_profiles.clone();
+        matches.extend(profile.params.matched_profiles.clone().unwrap_or_default());
+        matches.sort();
+        matches.dedup();
+
+        Ok(ExportBundle {
+            format_version: EXPORT_FORMAT_VERSION,
+            exported_at: now,
+            user_id: user_id.to_string(),
+            received_rightswipes,
+            received_leftswipes,
+            received_likes,
+            matches,
+            notifications: profile.notifications.iter().cloned().collect(),
+            chats,
+            profile,
+        })
+    }
+
+    pub fn create_data_export(&mut self, user_id: &str, export_id: String, token: &str, now: u64) -> Result<ExportManifest, DDateError> {
+        let bundle = self.build_export_bundle(user_id, now)?;
+        let bytes = candid::encode_one(&bundle)
+            .map_err(|e| DDateError::Internal(format!("Failed to encode export: {}", e)))?;
+        let manifest = ExportManifest {
+            json_path: format!("{}{}?token={}", EXPORT_PATH_PREFIX, export_id, token),
+            export_id: export_id.clone(),
+            format_version: EXPORT_FORMAT_VERSION,
+            created_at: now,
+            expires_at: now.saturating_add(EXPORT_TTL_NANOS),
+            total_bytes: bytes.len() as u64,
+            chunk_size: EXPORT_CHUNK_SIZE as u64,
+            chunk_count: bytes.len().div_ceil(EXPORT_CHUNK_SIZE) as u64,
+            sha256: format!("{:x}", Sha256::digest(&bytes)),
+        };
+        let export = DataExport {
+            user_id: user_id.to_string(),
+            created_at: now,
+            format_version: EXPORT_FORMAT_VERSION,
+            bundle: bytes,
+            json_token_hash: Some(hash_token(token)),
+        };
+        // A user holds at most one export; a new one replaces whatever is still stored.
+        self.remove_data_exports(user_id);
+        self.data_exports.insert(export_id, Candid(export));
+        Ok(manifest)
+    }
+
+    pub fn data_export_chunk(&self, user_id: &str, export_id: &str, index: u64) -> Result<Vec<u8>, DDateError> {
+        let export = self
+            .data_exports
+            .get(&export_id.to_string())
+            .map(|export| export.0)
+            .filter(|export| export.user_id == user_id)
+            .ok_or_else(|| DDateError::NotFound(format!("Export '{}' not found", export_id)))?;
+        export
+            .bundle
+            .chunks(EXPORT_CHUNK_SIZE)
+            .nth(index as usize)
+            .map(<[u8]>::to_vec)
+            .ok_or_else(|| DDateError::InvalidInput(format!("Chunk {} is out of range", index)))
+    }
+
+    fn export_for_token(&self, export_id: &str, token: &str, now: u64) -> Option<DataExport> {
+        self.data_exports
+            .get(&export_id.to_string())
+            .map(|export| export.0)
+            .filter(|export| now.saturating_sub(export.created_at) <= EXPORT_TTL_NANOS)
+            .filter(|export| export.json_token_hash.as_deref() == Some(hash_token(token).as_slice()))
+    }
+
+    // Checks and burns the one-time token, returning the export it unlocked.
+    pub fn redeem_export_token(&mut self, export_id: &str, token: &str, now: u64) -> Option<DataExport> {
+        let mut export = self.export_for_token(export_id, token, now)?;
+        let redeemed = export.clone();
+        export.json_token_hash = None;
+        self.data_exports.insert(export_id.to_string(), Candid(export));
+        Some(redeemed)
+    }
+
+    pub fn remove_data_exports(&mut self, user_id: &str) {
+        let exports: Vec<String> = self
+            .data_exports
+            .iter()
+            .filter(|(_, export)| export.user_id == user_id)
+            .map(|(export_id, _)| export_id)
+            .collect();
+        for export_id in exports {
+            self.data_exports.remove(&export_id);
+        }
+    }
+
+    pub fn purge_expired_exports(&mut self, now: u64) -> u64 {
+        let expired: Vec<String> = self
+            .data_exports
+            .iter()
+            .filter(|(_, export)| now.saturating_sub(export.created_at) > EXPORT_TTL_NANOS)
+            .map(|(export_id, _)| export_id)
+            .collect();
+        for export_id in &expired {
+            self.data_exports.remove(export_id);
+        }
+        expired.len() as u64
+    }
+}
+
+fn render_json(export: &DataExport) -> HttpResponse {
+    let json = candid::decode_one::<ExportBundle>(&export.bundle)
+        .map_err(|e| e.to_string())
+        .and_then(|bundle| serde_json::to_vec_pretty(&bundle).map_err(|e| e.to_string()));
+    match json {
+        Ok(body) => HttpResponse {
+            status_code: 200,
+            headers: vec![
+                ("Content-Type".to_string(), "application/json".to_string()),
+                ("Content-Disposition".to_string(), format!("attachment; filename=\"ddate-export-{}.json\"", export.user_id)),
+                ("Cache-Control".to_string(), "no-store".to_string()),
+            ],
+            body,
+            upgrade: None,
+        },
+        Err(error) => HttpResponse::text(500, &format!("Failed to render export: {}", error)),
+    }
+}
+
+fn purge_expired_exports() {
+    let purged = mutate_state(|state| state.purge_expired_exports(ic_cdk::api::time()));
+    if purged > 0 {
+        ic_cdk::println!("Purged {} expired data exports", purged);
+    }
+}
+
+pub fn start_export_purge_timer() {
+    ic_cdk_timers::set_timer_interval(EXPORT_PURGE_INTERVAL, purge_expired_exports);
+}
+
+// Assembles the caller's data into a bundle that stays downloadable for a day, replacing any earlier export.
+// Deliberately open in every lifecycle state, so deactivated and locked owners can still get their data.
+#[update(guard = "is_anonymous")]
+pub async fn export_my_data(user_id: String) -> Result<ExportManifest, DDateError> {
+    read_state(|state| authorize_user(state, &user_id))?;
+
+    let random_bytes = raw_rand()
+        .await
+        .map_err(|e| DDateError::Internal(format!("Failed to generate export token: {:?}", e)))?
+        .0;
+    let (id_bytes, token_bytes) = random_bytes.split_at(random_bytes.len() / 2);
+    let export_id = hex_string(id_bytes);
+    let token = hex_string(token_bytes);
+
+    ic_cdk::println!("Exporting data of user_id {} as export {}", user_id, export_id);
+    mutate_state(|state| {
+        // The caller may have lost the account while raw_rand was in flight.
+        authorize_user(state, &user_id)?;
+        state.create_data_export(&user_id, export_id, &token, ic_cdk::api::time())
+    })
+}
+
+#[query(guard = "is_anonymous")]
+pub fn get_data_export_chunk(export_id: String, index: u64) -> Result<Vec<u8>, DDateError> {
+    read_state(|state| {
+        let user_id = caller_user_id(state)?;
+        state.data_export_chunk(&user_id, &export_id, index)
+    })
+}
+
+// Answers from a query when the token is wrong; a valid token is only burned in `http_request_update`.
+#[query]
+pub fn http_request(request: HttpRequest) -> HttpResponse {
+    let Some((export_id, token)) = parse_export_url(&request.url) else {
+        return HttpResponse::text(404, "Not found");
+    };
+    match read_state(|state| state.export_for_token(export_id, token, ic_cdk::api::time())) {
+        Some(_) => HttpResponse::upgrade(),
+        None => HttpResponse::text(404, "Export not found, expired or already downloaded"),
+    }
+}
+
+#[update]
+pub fn http_request_update(request: HttpRequest) -> HttpResponse {
+    let Some((export_id, token)) = parse_export_url(&request.url) else {
+        return HttpResponse::text(404, "Not found");
+    };
+    match mutate_state(|state| state.redeem_export_token(export_id, token, ic_cdk::api::time())) {
+        Some(export) => {
+            ic_cdk::println!("Served JSON export {} to {}", export_id, caller());
+            render_json(&export)
+        }
+        None => HttpResponse::text(404, "Export not found, expired or already downloaded"),
+    }
+}
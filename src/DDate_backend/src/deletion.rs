@@ -0,0 +1,127 @@
+use std::time::Duration;
+
+use candid::CandidType;
+use ic_cdk::{query, update};
+use serde::{Deserialize, Serialize};
+
+use crate::cascade::schedule_deletion_tick;
+use crate::errors::DDateError;
+use crate::guards::{authorize_user, is_anonymous};
+use crate::lifecycle::{AccountState, Actor};
+use crate::state_handler::{mutate_state, read_state, State};
+
+#[derive(Debug, Serialize, Clone, Deserialize, CandidType)]
+pub struct DeletionStatus {
+    pub account_state: AccountState,
+    pub requested_at: Option<u64>,
+    // When the account and everything tied to it will be removed; `None` unless deletion is pending.
+    pub scheduled_at: Option<u64>,
+}
+
+impl State {
+    // Starts the grace period and returns when it ends.
+    pub fn request_account_deletion(&mut self, user_id: &str, now: u64) -> Result<u64, DDateError> {
+        let mut profile = self
+            .user_profiles
+            .get(&user_id.to_string())
+            .ok_or_else(|| DDateError::NotFound(format!("User ID '{}' not found", user_id)))?;
+        let scheduled_at = now.saturating_add(self.config().deletion_grace_nanos());
+        profile.apply_transition(AccountState::PendingDeletion, Actor::Owner, now)?;
+        profile.deletion_scheduled_at = Some(scheduled_at);
+        self.save_profile(user_id.to_string(), profile);
+        Ok(scheduled_at)
+    }
+
+    pub fn cancel_account_deletion(&mut self, user_id: &str, now: u64) -> Result<(), DDateError> {
+        let profile = self
+            .user_profiles
+            .get(&user_id.to_string())
+            .ok_or_else(|| DDateError::NotFound(format!("User ID '{}' not found", user_id)))?;
+        if profile.account_state != AccountState::PendingDeletion {
+            return Err(DDateError::Conflict("No deletion is pending for this account".to_string()));
+        }
+        self.transition_account(user_id, AccountState::Active, Actor::Owner, now)?;
+        Ok(())
+    }
+
+    pub fn deletion_status(&self, user_id: &str) -> Result<DeletionStatus, DDateError> {
+        let profile = self
+            .user_profiles
+            .get(&user_id.to_string())
+            .ok_or_else(|| DDateError::NotFound(format!("User ID '{}' not found", user_id)))?;
+        let pending = profile.account_state == AccountState::PendingDeletion;
+        Ok(DeletionStatus {
+            account_state: profile.account_state,
+            requested_at: pending.then_some(profile.state_changed_at),
+            scheduled_at: profile.deletion_scheduled_at,
+        })
+    }
+
+    // Starts the cascade for `user_id` if its grace period is over. A timer left over from a cancelled
+    // request finds nothing to do.
+    pub fn run_due_deletion(&mut self, user_id: &str, now: u64) -> bool {
+        let due = self.user_profiles.get(&user_id.to_string()).is_some_and(|profile| {
+            profile.account_state == AccountState::PendingDeletion
+                && profile.deletion_scheduled_at.is_some_and(|scheduled_at| scheduled_at <= now)
+        });
+        if due {
+            self.start_cascading_delete(user_id, now);
+        }
+        due
+    }
+
+    fn pending_deletions(&self) -> Vec<(String, u64)> {
+        self.user_profiles
+            .iter()
+            .filter(|(_, profile)| profile.account_state == AccountState::PendingDeletion)
+            .filter_map(|(user_id, profile)| profile.deletion_scheduled_at.map(|scheduled_at| (user_id, scheduled_at)))
+            .collect()
+    }
+}
+
+fn schedule_deletion(user_id: String, scheduled_at: u64) {
+    let delay = Duration::from_nanos(scheduled_at.saturating_sub(ic_cdk::api::time()));
+    ic_cdk_timers::set_timer(delay, move || {
+        if mutate_state(|state| state.run_due_deletion(&user_id, ic_cdk::api::time())) {
+            ic_cdk::println!("Grace period of user ID {} is over; deleting the account", user_id);
+            schedule_deletion_tick();
+        }
+    });
+}
+
+// Re-arms the timers of every pending deletion, since timers do not survive upgrades.
+pub fn start_scheduled_deletion_timers() {
+    for (user_id, scheduled_at) in read_state(|state| state.pending_deletions()) {
+        schedule_deletion(user_id, scheduled_at);
+    }
+}
+
+// Hides the account and deletes it once the grace period from `CanisterConfig::deletion_grace_days` ends,
+// unless the owner cancels first.
+#[update(guard = "is_anonymous")]
+pub fn request_account_deletion(user_id: String) -> Result<DeletionStatus, DDateError> {
+    let scheduled_at = mutate_state(|state| {
+        authorize_user(state, &user_id)?;
+        state.request_account_deletion(&user_id, ic_cdk::api::time())
+    })?;
+    ic_cdk::println!("User ID {} requested deletion, scheduled at {}", user_id, scheduled_at);
+    schedule_deletion(user_id.clone(), scheduled_at);
+    read_state(|state| state.deletion_status(&user_id))
+}
+
+#[update(guard = "is_anonymous")]
+pub fn cancel_account_deletion(user_id: String) -> Result<String, DDateError> {
+    mutate_state(|state| {
+        authorize_user(state, &user_id)?;
+        state.cancel_account_deletion(&user_id, ic_cdk::api::time())?;
+        Ok(format!("Deletion of user ID {} has been cancelled.", user_id))
+    })
+}
+
+#[query(guard = "is_anonymous")]
+pub fn get_deletion_status(user_id: String) -> Result<DeletionStatus, DDateError> {
+    read_state(|state| {
+        authorize_user(state, &user_id)?;
+        state.deletion_status(&user_id)
+    })
+}
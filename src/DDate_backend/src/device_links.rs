@@ -0,0 +1,197 @@
+use candid::{CandidType, Principal};
+use ic_cdk::api::management_canister::main::raw_rand;
+use ic_cdk::{caller, query, update};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::errors::DDateError;
+use crate::guards::{caller_usable_user_id, caller_user_id, is_anonymous};
+use crate::state_handler::{mutate_state, read_state, Candid, State};
+
+// A new device has ten minutes to redeem a link code.
+const LINK_CODE_TTL_NANOS: u64 = 10 * 60 * 1_000_000_000;
+const LINK_CODE_LENGTH: usize = 12;
+
+#[derive(Debug, Serialize, Clone, Deserialize, CandidType)]
+pub struct LinkedPrincipal {
+    pub principal: Principal,
+    pub linked_at: u64,
+}
+
+#[derive(Debug, Serialize, Clone, Deserialize, CandidType)]
+pub struct LinkCode {
+    pub code: String,
+    pub user_id: String,
+    pub issued_by: Principal,
+    pub expires_at: u64,
+}
+
+impl State {
+    // Principals linked to `user_id` in addition to its creator_principal.
+    pub fn linked_principals_for(&self, user_id: &str) -> Vec<LinkedPrincipal> {
+        self.linked_principals
+            .get(&user_id.to_string())
+            .map(|linked| linked.0)
+            .unwrap_or_default()
+    }
+
+    pub fn issue_link_code(&mut self, user_id: String, issued_by: Principal, code: String, now: u64) -> LinkCode {
+        self.purge_expired_link_codes(now);
+
+        let link_code = LinkCode {
+            code: code.clone(),
+            user_id,
+            issued_by,
+            expires_at: now + LINK_CODE_TTL_NANOS,
+        };
+        self.link_codes.insert(code, Candid(link_code.clone()));
+        link_code
+    }
+
+    // A code is only used up by a successful link; expired codes are purged first.
+    pub fn redeem_link_code(&mut self, code: &str, principal: Principal, now: u64) -> Result<String, DDateError> {
+        self.purge_expired_link_codes(now);
+
+        let link_code = self
+            .link_codes
+            .get(&code.to_string())
+            .ok_or_else(|| DDateError::NotFound("Link code is invalid or has expired".to_string()))?
+            .0;
+        let user_id = link_code.user_id;
+
+        let profile = self
+            .user_profiles
+            .get(&user_id)
+            .ok_or_else(|| DDateError::NotFound(format!("User ID '{}' not found", user_id)))?;
+        if !profile.account_state.is_usable() {
+            return Err(DDateError::Inactive("Account is inactive".to_string()));
+        }
+
+        let mut linked = self.linked_principals_for(&user_id);
+        if profile.creator_principal == principal || linked.iter().any(|l| l.principal == principal) {
+            return Err(DDateError::Conflict(format!("Principal {} is already linked to user ID '{}'", principal, user_id)));
+        }
+
+        self.index_principal(principal, user_id.clone())?;
+        linked.push(LinkedPrincipal {
+            principal,
+            linked_at: now,
+        });
+        self.linked_principals.insert(user_id.clone(), Candid(linked));
+        self.link_codes.remove(&code.to_string());
+
+        ic_cdk::println!("Linked principal {} to user_id: {}", principal, user_id);
+        Ok(user_id)
+    }
+
+    pub fn revoke_linked_principal(&mut self, user_id: &str, principal: &Principal) -> Result<String, DDateError> {
+        let profile = self
+            .user_profiles
+            .get(&user_id.to_string())
+            .ok_or_else(|| DDateError::NotFound(format!("User ID '{}' not found", user_id)))?;
+        if profile.creator_principal == *principal {
+            return Err(DDateError::InvalidInput("The creator principal cannot be revoked".to_string()));
+        }
+
+        let mut linked = self.linked_principals_for(user_id);
+        let before = linked.len();
+        linked.retain(|l| l.principal != *principal);
+        if linked.len() == before {
+            return Err(DDateError::NotFound(format!("Principal {} is not linked to user ID '{}'", principal, user_id)));
+        }
+
+        self.unindex_principal(principal, user_id);
+        self.linked_principals.insert(user_id.to_string(), Candid(linked));
+
+        // Codes handed out by the revoked device must not outlive it.
+        let issued: Vec<String> = self
+            .link_codes
+            .iter()
+            .filter(|(_, link_code)| link_code.issued_by == *principal)
+            .map(|(code, _)| code)
+            .collect();
+        for code in issued {
+            self.link_codes.remove(&code);
+        }
+
+        Ok(format!("Revoked principal {} from user ID '{}'", principal, user_id))
+    }
+
+    // Forgets every linked principal and outstanding link code of a removed account.
+    pub fn remove_device_links(&mut self, user_id: &str) {
+        self.linked_principals.remove(&user_id.to_string());
+
+        let codes: Vec<String> = self
+            .link_codes
+            .iter()
+            .filter(|(_, link_code)| link_code.user_id == user_id)
+            .map(|(code, _)| code)
+            .collect();
+        for code in codes {
+            self.link_codes.remove(&code);
+        }
+    }
+
+    fn purge_expired_link_codes(&mut self, now: u64) {
+        let expired: Vec<String> = self
+            .link_codes
+            .iter()
+            .filter(|(_, link_code)| link_code.expires_at <= now)
+            .map(|(code, _)| code)
+            .collect();
+        for code in expired {
+            self.link_codes.remove(&code);
+        }
+    }
+}
+
+#[update(guard = "is_anonymous")]
+pub async fn create_link_code() -> Result<LinkCode, DDateError> {
+    let principal = caller();
+    let user_id = read_state(caller_usable_user_id)?;
+
+    let random_bytes = raw_rand()
+        .await
+        .map_err(|e| DDateError::Internal(format!("Failed to generate link code: {:?}", e)))?
+        .0;
+    let code = format!("{:x}", Sha256::digest(&random_bytes))[..LINK_CODE_LENGTH].to_uppercase();
+
+    ic_cdk::println!("Issuing link code for user_id: {}", user_id);
+    mutate_state(|state| {
+        // The account may have been deactivated or locked while raw_rand was in flight.
+        caller_usable_user_id(state)?;
+        Ok(state.issue_link_code(user_id, principal, code, ic_cdk::api::time()))
+    })
+}
+
+#[update(guard = "is_anonymous")]
+pub fn redeem_link_code(code: String) -> Result<String, DDateError> {
+    let principal = caller();
+    mutate_state(|state| state.redeem_link_code(code.trim(), principal, ic_cdk::api::time()))
+}
+
+#[query(guard = "is_anonymous")]
+pub fn get_linked_principals() -> Result<Vec<LinkedPrincipal>, DDateError> {
+    read_state(|state| {
+        let user_id = caller_user_id(state)?;
+        let profile = state
+            .user_profiles
+            .get(&user_id)
+            .ok_or_else(|| DDateError::NotFound(format!("User ID '{}' not found", user_id)))?;
+
+        let mut principals = vec![LinkedPrincipal {
+            principal: profile.creator_principal,
+            linked_at: profile.created_at,
+        }];
+        principals.extend(state.linked_principals_for(&user_id));
+        Ok(principals)
+    })
+}
+
+#[update(guard = "is_anonymous")]
+pub fn revoke_linked_principal(principal: Principal) -> Result<String, DDateError> {
+    mutate_state(|state| {
+        let user_id = caller_user_id(state)?;
+        state.revoke_linked_principal(&user_id, &principal)
+    })
+}
@@ -0,0 +1,53 @@
+use std::fmt;
+
+use candid::CandidType;
+use serde::{Deserialize, Serialize};
+
+use crate::validation::FieldError;
+
+// Error returned by every endpoint, so clients can branch on the variant instead of the message.
+#[derive(Debug, Serialize, Clone, Deserialize, CandidType, PartialEq)]
+pub enum DDateError {
+    NotFound(String),
+    Inactive(String),
+    Unauthorized(String),
+    Validation(Vec<FieldError>),
+    InvalidInput(String),
+    RateLimited(String),
+    Conflict(String),
+    // The record changed since the caller read it; re-read and retry against `current_version`.
+    VersionConflict { expected_version: u64, current_version: u64 },
+    Internal(String),
+}
+
+impl From<Vec<FieldError>> for DDateError {
+    fn from(errors: Vec<FieldError>) -> Self {
+        DDateError::Validation(errors)
+    }
+}
+
+impl fmt::Display for DDateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DDateError::NotFound(message) => write!(f, "Not found: {}", message),
+            DDateError::Inactive(message) => write!(f, "Inactive: {}", message),
+            DDateError::Unauthorized(message) => write!(f, "Unauthorized: {}", message),
+            DDateError::Validation(errors) => {
+                let fields: Vec<String> = errors.iter().map(|e| format!("{} {}", e.field, e.message)).collect();
+                write!(f, "Validation failed: {}", fields.join("; "))
+            }
+            DDateError::InvalidInput(message) => write!(f, "Invalid input: {}", message),
+            DDateError::RateLimited(message) => write!(f, "Rate limited: {}", message),
+            DDateError::Conflict(message) => write!(f, "Conflict: {}", message),
+            DDateError::VersionConflict {
+                expected_version,
+                current_version,
+            } => write!(
+                f,
+                "Conflict: expected version {} but the current version is {}",
+                expected_version, current_version
+            ),
+            DDateError::Internal(message) => write!(f, "Internal error: {}", message),
+        }
+    }
+}
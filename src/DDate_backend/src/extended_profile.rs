@@ -0,0 +1,108 @@
+use candid::CandidType;
+use serde::{Deserialize, Serialize};
+
+// Optional "more about me" details shown below the core profile.
+#[derive(Debug, Serialize, Clone, Default, Deserialize, CandidType, PartialEq)]
+pub struct ExtendedProfile {
+    pub height_cm: Option<u64>,
+    pub occupation: Option<String>,
+    pub looking_for: Option<String>,
+    pub pets: Option<String>,
+    pub art_and_culture: Option<Vec<String>>,
+    pub general_habits: Option<Vec<String>>,
+    pub outdoor_activities: Option<Vec<String>>,
+    pub travel: Option<Vec<String>>,
+    pub movies: Option<Vec<String>>,
+}
+
+impl ExtendedProfile {
+    pub fn merge(&mut self, other: ExtendedProfile) {
+        if let Some(height_cm) = other.height_cm {
+            self.height_cm = Some(height_cm);
+        }
+        if let Some(occupation) = other.occupation {
+            self.occupation = Some(occupation);
+        }
+        if let Some(looking_for) = other.looking_for {
+            self.looking_for = Some(looking_for);
+        }
+        if let Some(pets) = other.pets {
+            self.pets = Some(pets);
+        }
+        if let Some(art_and_culture) = other.art_and_culture {
+            self.art_and_culture = Some(art_and_culture);
+        }
+        if let Some(general_habits) = other.general_habits {
+            self.general_habits = Some(general_habits);
+        }
+        if let Some(outdoor_activities) = other.outdoor_activities {
+            self.outdoor_activities = Some(outdoor_activities);
+        }
+        if let Some(travel) = other.travel {
+            self.travel = Some(travel);
+        }
+        if let Some(movies) = other.movies {
+            self.movies = Some(movies);
+        }
+    }
+}
+
+// Narrows discovery to profiles whose extended section fits. Every field left as `None` is ignored;
+// a profile that leaves a filtered field empty does not match.
+#[derive(Debug, Serialize, Clone, Default, Deserialize, CandidType)]
+pub struct ExtendedProfileFilter {
+    pub min_height_cm: Option<u64>,
+    pub max_height_cm: Option<u64>,
+    pub occupation: Option<String>,
+    pub looking_for: Option<String>,
+    pub pets: Option<String>,
+    // List filters match profiles that share at least one entry with the filter.
+    pub art_and_culture: Option<Vec<String>>,
+    pub general_habits: Option<Vec<String>>,
+    pub outdoor_activities: Option<Vec<String>>,
+    pub travel: Option<Vec<String>>,
+    pub movies: Option<Vec<String>>,
+}
+
+fn same_text(a: &str, b: &str) -> bool {
+    a.trim().eq_ignore_ascii_case(b.trim())
+}
+
+fn text_matches(wanted: &Option<String>, value: &Option<String>) -> bool {
+    match wanted {
+        None => true,
+        Some(wanted) => value.as_deref().is_some_and(|value| same_text(wanted, value)),
+    }
+}
+
+fn list_matches(wanted: &Option<Vec<String>>, value: &Option<Vec<String>>) -> bool {
+    match wanted {
+        None => true,
+        Some(wanted) => value
+            .iter()
+            .flatten()
+            .any(|item| wanted.iter().any(|wanted| same_text(wanted, item))),
+    }
+}
+
+impl ExtendedProfileFilter {
+    pub fn matches(&self, extended: Option<&ExtendedProfile>) -> bool {
+        let empty = ExtendedProfile::default();
+        let extended = extended.unwrap_or(&empty);
+        let height_fits = match (self.min_height_cm, self.max_height_cm) {
+            (None, None) => true,
+            (min, max) => extended
+                .height_cm
+                .is_some_and(|height| min.is_none_or(|min| height >= min) && max.is_none_or(|max| height <= max)),
+        };
+        height_fits
+            && text_matches(&self.occupation, &extended.occupation)
+            && text_matches(&self.looking_for, &extended.looking_for)
+            && text_matches(&self.pets, &extended.pets)
+            && list_matches(&self.art_and_culture, &extended.art_and_culture)
+            && list_matches(&self.general_habits, &extended.general_habits)
+            && list_matches(&self.outdoor_activities, &extended.outdoor_activities)
+            && list_matches(&self.travel, &extended.travel)
+            && list_matches(&self.movies, &extended.movies)
+    }
+}
@@ -0,0 +1,81 @@
+use candid::Principal;
+use ic_cdk::caller;
+
+use crate::errors::DDateError;
+use crate::roles::Role;
+use crate::state_handler::{read_state, State};
+
+const ANONYMOUS_PRINCIPAL_ID: &str = "2vxsx-fae";
+
+// Guard for `#[query]`/`#[update]` endpoints: rejects calls made without an identity.
+pub fn is_anonymous() -> Result<(), String> {
+    let caller = caller();
+    if caller == Principal::anonymous() || caller.to_text() == ANONYMOUS_PRINCIPAL_ID {
+        Err("Access denied: Caller is anonymous.".to_string())
+    } else {
+        Ok(())
+    }
+}
+
+fn require_role(required: Role) -> Result<(), String> {
+    let caller = caller();
+    if read_state(|state| state.has_role(&caller, required)) {
+        Ok(())
+    } else {
+        Err(format!("Access denied: Caller requires the {:?} role.", required))
+    }
+}
+
+// Guards for operator endpoints; each role also admits every role above it.
+pub fn is_controller() -> Result<(), String> {
+    require_role(Role::Controller)
+}
+
+pub fn is_admin() -> Result<(), String> {
+    require_role(Role::Admin)
+}
+
+pub fn is_moderator() -> Result<(), String> {
+    require_role(Role::Moderator)
+}
+
+// Returns the user_id owned by the caller, whatever its lifecycle state, or an error if the caller has no account.
+// Endpoints that must refuse deactivated or locked accounts check the state themselves or use `caller_usable_user_id`.
+pub fn caller_user_id(state: &State) -> Result<String, DDateError> {
+    let principal = caller();
+    state
+        .find_user_id_by_principal(&principal)
+        .ok_or_else(|| DDateError::NotFound(format!("No account found for principal ID: {}", principal)))
+}
+
+fn require_usable(state: &State, user_id: &str) -> Result<(), DDateError> {
+    match state.user_profiles.get(&user_id.to_string()) {
+        Some(profile) if profile.account_state.is_usable() => Ok(()),
+        _ => Err(DDateError::Inactive("Account is inactive".to_string())),
+    }
+}
+
+// Like `caller_user_id`, but only for an account the owner can currently use.
+pub fn caller_usable_user_id(state: &State) -> Result<String, DDateError> {
+    let user_id = caller_user_id(state)?;
+    require_usable(state, &user_id)?;
+    Ok(user_id)
+}
+
+// Ensures the caller owns `user_id` before acting on it.
+pub fn authorize_user(state: &State, user_id: &str) -> Result<(), DDateError> {
+    let caller_id = caller_user_id(state).map_err(|_| {
+        DDateError::Unauthorized(format!("Caller has no account to act on user ID '{}'", user_id))
+    })?;
+    if caller_id == user_id {
+        Ok(())
+    } else {
+        Err(DDateError::Unauthorized(format!("Caller does not own user ID '{}'", user_id)))
+    }
+}
+
+// Like `authorize_user`, but also requires the account to be usable.
+pub fn authorize_usable_user(state: &State, user_id: &str) -> Result<(), DDateError> {
+    authorize_user(state, user_id)?;
+    require_usable(state, user_id)
+}
@@ -1,76 +1,105 @@
+#![allow(non_snake_case)]
+
+mod age;
+mod attributes;
+mod audit;
+mod cascade;
+mod completeness;
+mod config;
+mod data_export;
+mod deletion;
+mod device_links;
+mod errors;
+mod extended_profile;
+mod guards;
+mod lifecycle;
+mod migrations;
+mod onboarding;
+mod pause;
+mod principal_index;
+mod privacy;
 mod profile_creation;
 mod notification;
 mod profile_matcher;
+mod profile_patch;
+mod profile_views;
+mod recovery;
 mod right_and_left_swipe;
+mod roles;
 mod state_handler;
+mod timers;
+mod upgrade;
+mod validation;
 use std::collections::VecDeque;
 use crate::profile_creation::UserProfileCreationInfo;
 use candid::CandidType;
-// use candid::Principal;
+use candid::Principal;
 use ic_cdk::{caller, export_candid, query, update};
+pub use attributes::{Diet, Drinking, Gender, GenderPronouns, Religion, Smoking, Zodiac};
+pub use audit::*;
+pub use cascade::*;
+pub use completeness::*;
+pub use config::*;
+pub use data_export::*;
+pub use deletion::*;
+pub use device_links::*;
+pub use migrations::*;
 pub use notification::*;
+pub use onboarding::*;
+pub use pause::*;
 use profile_creation::Message;
 use profile_creation::{Notification, Pagination};
 pub use profile_matcher::*;
+pub use profile_patch::*;
+pub use privacy::{PrivacySettings, Visibility};
+pub use profile_views::*;
+pub use recovery::*;
 pub use right_and_left_swipe::*;
+pub use roles::*;
+pub use upgrade::*;
 use serde::Serialize;
 use state_handler::*;
+pub use errors::DDateError;
+pub use extended_profile::{ExtendedProfile, ExtendedProfileFilter};
+pub use lifecycle::*;
+pub use validation::FieldError;
 use crate::profile_creation::UserInputParams;
 use crate::profile_creation::PaginatedProfiles;
+use guards::{authorize_usable_user, authorize_user, caller_user_id, is_anonymous};
 
-// const ANONYMOUS_PRINCIPAL_ID: &str = "2vxsx-fae";
-
-// fn is_anonymous() -> Result<(), String> {
-//     let caller = ic_cdk::caller();
-//     if caller == Principal::anonymous() || caller.to_text() == ANONYMOUS_PRINCIPAL_ID {
-//         Err("Access denied: Caller is anonymous.".to_string())
-//     } else {
-//         Ok(())
-//     }
-// }
-
-// #[query(guard = "is_anonymous")]
-
-
-
-// #[update(guard = "is_anonymous")]
-#[update]
-pub fn add_user_to_chatlist(user_id: String) -> Result<Vec<ChatListItem>, String> {
+#[update(guard = "is_anonymous")]
+pub fn add_user_to_chatlist(user_id: String) -> Result<Vec<ChatListItem>, DDateError> {
     ic_cdk::println!("Adding user to chatlist with user_id: {}", user_id);
+    read_state(|state| authorize_user(state, &user_id))?;
 
-    // Call get_rightswiped_matches with page = 1 and size = 100 to get the latest matched profiles
-    let match_result = get_rightswiped_matches(user_id.clone(), 1, 100)?;
+    // Call get_rightswiped_matches with page = 1 and the largest allowed size to get the latest matched profiles
+    let max_page_size = read_state(|state| state.config().max_page_size) as usize;
+    let match_result = get_rightswiped_matches(user_id.clone(), 1, max_page_size)?;
 
     read_state(|state| {
         let user_profile = state
             .user_profiles
             .get(&user_id)
-            .ok_or_else(|| format!("User ID '{}' not found", user_id))?;
+            .ok_or_else(|| DDateError::NotFound(format!("User ID '{}' not found", user_id)))?;
 
-        if !user_profile.status {
-            return Err("User account is inactive".to_string());
+        if !user_profile.account_state.is_usable() {
+            return Err(DDateError::Inactive("User account is inactive".to_string()));
         }
 
         let mut chatlist: Vec<ChatListItem> = Vec::new();
 
-        for matched_user_id in match_result.paginated_profiles.iter().map(|profile| profile.user_id.clone()) {
-            let matched_user_profile = state
-                .user_profiles
-                .get(&matched_user_id)
-                .ok_or_else(|| format!("Matched User ID '{}' not found", matched_user_id))?;
+        // Matched profiles are already filtered by each user's privacy settings
+        for matched_profile in match_result.paginated_profiles {
+            let matched_user_id = matched_profile.user_id;
 
-            // Example: Get name from matched_user_profile (UserProfileParams)
-            let name = matched_user_profile
-                .params
+            // Example: Get name from matched_profile (PublicProfile)
+            let name = matched_profile
                 .name
-                .clone()
                 .unwrap_or_else(|| "Unknown".to_string());
 
-            // Example: Get image from matched_user_profile (UserProfileParams)
-            let image = matched_user_profile
-                .params
+            // Example: Get image from matched_profile (PublicProfile)
+            let image = matched_profile
                 .images
-                .clone()
                 .unwrap_or_else(|| vec!["default.jpg".to_string()]);
 
             // Example: Get content (message) from Message struct
@@ -113,38 +142,27 @@ pub struct ChatListItem {
 
 
 
-// #[query(guard = "is_anonymous")]
-#[query]
-fn get_user_id_by_principal() -> Result<String, String> {
-    let principal = caller();
-    ic_cdk::println!("principal: {}",principal);
+#[query(guard = "is_anonymous")]
+fn get_user_id_by_principal() -> Result<String, DDateError> {
+    ic_cdk::println!("principal: {}", caller());
 
-    let user_id = read_state(|state| {
-        state.user_profiles.iter().find(|(_, profile)| {
-            profile.creator_principal == principal && profile.status == true
-        }).map(|(user_id, _)| user_id.clone())
-    });
-
-    match user_id {
-        Some(id) => Ok(id),
-        None => Err(format!("No active account found for principal ID: {}", principal)),
-    }
+    read_state(caller_user_id)
 }
 
-// #[update(guard = "is_anonymous")]
-#[update]
-pub fn send_like_notification_candid(sender_id: String, receiver_id: String) -> Result<(), String> {
+#[update(guard = "is_anonymous")]
+pub fn send_like_notification_candid(sender_id: String, receiver_id: String) -> Result<(), DDateError> {
     ic_cdk::println!("Sender ID: {}", sender_id);
     ic_cdk::println!("Receiver ID: {}", receiver_id);
 
     STATE.with(|state| {
         let mut state = state.borrow_mut();
+        authorize_user(&state, &sender_id)?;
         ic_cdk::println!("Profiles available: {:?}", state.user_profiles.iter().map(|(k, _)| k).collect::<Vec<_>>());
 
         // Check if sender profile exists
         let _sender_profile = match state.user_profiles.get(&sender_id) {
             Some(profile) => profile,
-            None => return Err(format!("Sender profile not found: {}", sender_id)),
+            None => return Err(DDateError::NotFound(format!("Sender profile not found: {}", sender_id))),
         };
 
         // Check if receiver profile exists and send notification
@@ -152,14 +170,14 @@ pub fn send_like_notification_candid(sender_id: String, receiver_id: String) ->
     })
 }
 
-// #[update(guard = "is_anonymous")]
-#[update]
+#[update(guard = "is_anonymous")]
 pub fn get_rightswiped_matches(
     user_id: String,
     page: usize,
     size: usize,
-) -> Result<MatchResult, String> {
+) -> Result<MatchResult, DDateError> {
     ic_cdk::println!("Finding matches for user: {}", user_id);
+    read_state(|state| authorize_user(state, &user_id))?;
 
     let user_profile_exists = STATE.with(|state| {
         state
@@ -169,38 +187,29 @@ pub fn get_rightswiped_matches(
     });
 
     if !user_profile_exists {
-        return Err(format!("User ID '{}' does not exist or is inactive", user_id));
-    }
-
-    if page == 0 {
-        return Err("Page number must be greater than 0".to_string());
+        return Err(DDateError::NotFound(format!("User ID '{}' does not exist or is inactive", user_id)));
     }
 
     let pagination = Pagination { page, size };
+    read_state(|state| state.validate_pagination(&pagination))?;
 
     let match_result = find_matches(&user_id, pagination)?;
 
     STATE.with(|state| {
         let mut state = state.borrow_mut();
-        // Remove the old profile, update matched_profiles, and reinsert it
-        if let Some(mut user_profile) = state.user_profiles.remove(&user_id) {
-            // Ensure we only include profiles that have mutually right-swiped
+        // Update matched_profiles and write the profile back
+        if let Some(mut user_profile) = state.user_profiles.get(&user_id) {
+            // find_matches only returns profiles that have mutually right-swiped
             let valid_matched_profiles: Vec<String> = match_result
                 .paginated_profiles
                 .iter()
-                .filter(|profile| {
-                    profile
-                        .params
-                        .rightswipes
-                        .as_ref()
-                        .map_or(false, |rightswipes| rightswipes.contains(&user_id))
-                        && user_profile.params.rightswipes.as_ref().map_or(false, |rightswipes| rightswipes.contains(&profile.user_id))
-                })
-                .map(|profile| profile.user_id.clone())
+                .map(|matched| matched.user_id.clone())
                 .collect();
 
-            user_profile.matched_profiles = valid_matched_profiles;
-            state.user_profiles.insert(user_id.clone(), user_profile);
+            if user_profile.matched_profiles != valid_matched_profiles {
+                user_profile.matched_profiles = valid_matched_profiles;
+                state.save_profile(user_id.clone(), user_profile);
+            }
         }
     });
 
@@ -209,54 +218,52 @@ pub fn get_rightswiped_matches(
 
 
 
-// #[update(guard = "is_anonymous")]
-#[update]
-fn leftswipe(input: SwipeInput) -> String {
+#[update(guard = "is_anonymous")]
+fn leftswipe(input: SwipeInput) -> Result<String, DDateError> {
     mutate_state(|state| {
-        match leftswipe_profile(state, input) {
-            Ok(message) => message,
-            Err(error) => error,
-        }
+        authorize_user(state, &input.sender_id)?;
+        leftswipe_profile(state, input)
     })
 }
 
-// #[update(guard = "is_anonymous")]
-#[update]
-fn rightswipe(input: SwipeInput) -> String {
+#[update(guard = "is_anonymous")]
+fn rightswipe(input: SwipeInput) -> Result<String, DDateError> {
     mutate_state(|state| {
-        match rightswipe_profile(state, input) {
-            Ok(message) => message,
-            Err(error) => error,
-        }
+        authorize_user(state, &input.sender_id)?;
+        rightswipe_profile(state, input)
     })
 }
 
 
-// #[query(guard = "is_anonymous")]
-#[query]
-fn get_leftswipes(user_id: String, pagination: Pagination) -> Result<MatchResult, String> {
+#[query(guard = "is_anonymous")]
+fn get_leftswipes(user_id: String, pagination: Pagination) -> Result<SwipedProfiles, DDateError> {
     read_state(|state| {
+        authorize_user(state, &user_id)?;
         fetch_leftswipes(state, user_id, pagination)
     })
 }
 
 
-// #[query(guard = "is_anonymous")]
-#[query]
-fn get_rightswipes(user_id: String, pagination: Pagination) -> Result<MatchResult, String> {
+#[query(guard = "is_anonymous")]
+fn get_rightswipes(user_id: String, pagination: Pagination) -> Result<SwipedProfiles, DDateError> {
     read_state(|state| {
+        authorize_user(state, &user_id)?;
         fetch_rightswipes(state, user_id, pagination)
     })
 }
 
-// #[update(guard = "is_anonymous")]
-#[update]
+#[update(guard = "is_anonymous")]
 fn check_user_match(current_user_id: String, potential_match_id: String) -> bool {
     // Check if current_user_id is the same as potential_match_id
     if current_user_id == potential_match_id {
         ic_cdk::println!("Error: Same profile ID entered in both fields.");
         return false;  // Return early and do not proceed
     }
+
+    if let Err(error) = read_state(|state| authorize_usable_user(state, &current_user_id)) {
+        ic_cdk::println!("Error: {}", error);
+        return false;
+    }
     
     read_state(|state| {
         ic_cdk::println!("Checking match between {} and {}", current_user_id, potential_match_id);
@@ -280,8 +287,8 @@ fn check_user_match(current_user_id: String, potential_match_id: String) -> bool
                 && current_user.params.location_city == potential_match.params.preferred_city
                 && current_user.params.location_state == potential_match.params.preferred_state
                 && current_user.params.location_country == potential_match.params.preferred_country
-                && current_user.params.rightswipes.as_ref().map_or(false, |rightswipes| rightswipes.contains(&potential_match_id))
-                && potential_match.params.rightswipes.as_ref().map_or(false, |rightswipes| rightswipes.contains(&current_user_id));
+                && current_user.params.rightswipes.as_ref().is_some_and(|rightswipes| rightswipes.contains(&potential_match_id))
+                && potential_match.params.rightswipes.as_ref().is_some_and(|rightswipes| rightswipes.contains(&current_user_id));
             
             ic_cdk::println!("Match result for {} and {}: {}", current_user_id, potential_match_id, match_found);
             match_found
@@ -292,56 +299,54 @@ fn check_user_match(current_user_id: String, potential_match_id: String) -> bool
     })
 }
 
-// #[update(guard = "is_anonymous")]
-#[update]
-fn remove_user_matches(user_id: String) -> Result<String, String> {
+#[update(guard = "is_anonymous")]
+fn remove_user_matches(user_id: String) -> Result<String, DDateError> {
     mutate_state(|state| {
+        authorize_user(state, &user_id)?;
         remove_matches(state, user_id)
     })
 }
 
-// #[update(guard = "is_anonymous")]
-#[update]
-fn make_user_inactive(user_id: String) -> Result<String, String> {
-    mutate_state(|state| {
-        state.set_user_inactive(user_id)
-    })
+// Deprecated: kept for older clients, use `deactivate_account`.
+#[update(guard = "is_anonymous")]
+fn make_user_inactive(user_id: String) -> Result<String, DDateError> {
+    deactivate_account(user_id)
 }
 
-// #[update(guard = "is_anonymous")]
-#[update]
-pub fn retrieve_notifications_for_user(user_id: String) -> Result<Vec<Notification>, String> {
+#[update(guard = "is_anonymous")]
+pub fn retrieve_notifications_for_user(user_id: String) -> Result<Vec<Notification>, DDateError> {
     read_state(|state| {
+        authorize_user(state, &user_id)?;
         // Check if the user ID exists and is active
         if let Some(profile) = state.user_profiles.get(&user_id) {
-            if !profile.status {
-                return Err("User's account is inactive".to_string());
+            if !profile.account_state.is_usable() {
+                return Err(DDateError::Inactive("User's account is inactive".to_string()));
             }
             // Retrieve notifications if user exists
             let notifications = profile.notifications.iter().cloned().collect();
             Ok(notifications)
         } else {
             // Return an error if user does not exist
-            Err(format!("User ID '{}' does not exist", user_id))
+            Err(DDateError::NotFound(format!("User ID '{}' does not exist", user_id)))
         }
     })
 }
 
-// #[update(guard = "is_anonymous")]
-#[update]
-pub fn create_message(sender_id: String, receiver_id: String, content: String) -> Result<u64, String> {
+#[update(guard = "is_anonymous")]
+pub fn create_message(sender_id: String, receiver_id: String, content: String) -> Result<u64, DDateError> {
     STATE.with(|state| {
         let mut state = state.borrow_mut();
+        authorize_user(&state, &sender_id)?;
 
-        let sender_profile = state.user_profiles.get(&sender_id).ok_or_else(|| format!("Sender ID {} does not exist.", sender_id))?;
-        let receiver_profile = state.user_profiles.get(&receiver_id).ok_or_else(|| format!("Receiver ID {} does not exist.", receiver_id))?;
+        let sender_profile = state.user_profiles.get(&sender_id).ok_or_else(|| DDateError::NotFound(format!("Sender ID {} does not exist.", sender_id)))?;
+        let receiver_profile = state.user_profiles.get(&receiver_id).ok_or_else(|| DDateError::NotFound(format!("Receiver ID {} does not exist.", receiver_id)))?;
 
-        if !sender_profile.status {
-            return Err("Sender's account is inactive".to_string());
+        if !sender_profile.account_state.can_message() {
+            return Err(DDateError::Inactive("Sender's account is inactive".to_string()));
         }
 
-        if !receiver_profile.status {
-            return Err("Receiver's account is inactive".to_string());
+        if !receiver_profile.account_state.can_message() {
+            return Err(DDateError::Inactive("Receiver's account is inactive".to_string()));
         }
 
         state.create_message_internal(sender_id, receiver_id, content)
@@ -349,30 +354,32 @@ pub fn create_message(sender_id: String, receiver_id: String, content: String) -
 }
 
 
-// #[query(guard = "is_anonymous")]
-#[query]
-pub fn read_messages(user_id: String, other_user_id: String) -> Result<Vec<Message>, String> {
+#[query(guard = "is_anonymous")]
+pub fn read_messages(user_id: String, other_user_id: String) -> Result<Vec<Message>, DDateError> {
     STATE.with(|state| {
         let state = state.borrow();
+        authorize_user(&state, &user_id)?;
 
-        let user_profile = state.user_profiles.get(&user_id).ok_or_else(|| format!("User ID {} does not exist.", user_id))?;
-        let other_user_profile = state.user_profiles.get(&other_user_id).ok_or_else(|| format!("Other user ID {} does not exist.", other_user_id))?;
+        let user_profile = state.user_profiles.get(&user_id).ok_or_else(|| DDateError::NotFound(format!("User ID {} does not exist.", user_id)))?;
+        let other_user_profile = state.user_profiles.get(&other_user_id).ok_or_else(|| DDateError::NotFound(format!("Other user ID {} does not exist.", other_user_id)))?;
 
-        if !user_profile.status {
-            return Err("User's account is inactive".to_string());
+        if !user_profile.account_state.can_message() {
+            return Err(DDateError::Inactive("User's account is inactive".to_string()));
         }
 
-        if !other_user_profile.status {
-            return Err("Other user's account is inactive".to_string());
+        if !other_user_profile.account_state.can_message() {
+            return Err(DDateError::Inactive("Other user's account is inactive".to_string()));
         }
 
         state_handler::State::read_messages(&user_id, &other_user_id)
     })
 }
 
-// #[update(guard = "is_anonymous")]
-#[update]
-pub fn update_message(timestamp: u64, new_content: String) -> Result<String, String> {
+#[update(guard = "is_anonymous")]
+pub fn update_message(timestamp: u64, new_content: String) -> Result<String, DDateError> {
+    let caller_id = read_state(caller_user_id)?;
+    read_state(|state| state.validate_message_length(&new_content))?;
+
     STATE.with(|state| {
         let mut sender_id = None;
 
@@ -388,6 +395,9 @@ pub fn update_message(timestamp: u64, new_content: String) -> Result<String, Str
                     let mut messages_data: VecDeque<Message> = candid_messages.0.clone();
 
                     if let Some(message) = messages_data.iter_mut().find(|message| message.timestamp == timestamp) {
+                        if message.sender_id != caller_id {
+                            return Err(DDateError::Unauthorized("Access denied: Only the sender can update this message".to_string()));
+                        }
                         sender_id = Some(message.sender_id.clone());
                         message.content = new_content.clone();
                         message_found = true;
@@ -406,25 +416,25 @@ pub fn update_message(timestamp: u64, new_content: String) -> Result<String, Str
 
             if let Some(sender_id) = sender_id {
                 let sender_profile = state.user_profiles.get(&sender_id).ok_or_else(|| {
-                    format!("Sender ID {} does not exist.", sender_id)
+                    DDateError::NotFound(format!("Sender ID {} does not exist.", sender_id))
                 })?;
-                if !sender_profile.status {
-                    return Err("Sender's account is inactive".to_string());
+                if !sender_profile.account_state.can_message() {
+                    return Err(DDateError::Inactive("Sender's account is inactive".to_string()));
                 }
             }
 
             Ok("Message updated successfully".to_string())
         } else {
-            Err("Message not found".to_string())
+            Err(DDateError::NotFound("Message not found".to_string()))
         }
     })
 }
 
-// #[update(guard = "is_anonymous")]
-#[update]
-pub fn delete_message(timestamp: u64) -> Result<String, String> {
+#[update(guard = "is_anonymous")]
+pub fn delete_message(timestamp: u64) -> Result<String, DDateError> {
     STATE.with(|state| {
         let mut state = state.borrow_mut();
+        let caller_id = caller_user_id(&state)?;
 
         // Find the message and check if the sender is active
         let mut sender_id = None;
@@ -446,16 +456,19 @@ pub fn delete_message(timestamp: u64) -> Result<String, String> {
         }
 
         if let Some(sender_id) = sender_id {
+            if sender_id != caller_id {
+                return Err(DDateError::Unauthorized("Access denied: Only the sender can delete this message".to_string()));
+            }
             let sender_profile = state.user_profiles.get(&sender_id).ok_or_else(|| {
-                format!("Sender ID {} does not exist.", sender_id)
+                DDateError::NotFound(format!("Sender ID {} does not exist.", sender_id))
             })?;
-            if !sender_profile.status {
-                return Err("Sender's account is inactive".to_string());
+            if !sender_profile.account_state.can_message() {
+                return Err(DDateError::Inactive("Sender's account is inactive".to_string()));
             }
             // Call the delete_message function to delete the message
             state_handler::State::delete_message(&mut state, timestamp)
         } else {
-            Err("Message not found".to_string())
+            Err(DDateError::NotFound("Message not found".to_string()))
         }
     })
 }
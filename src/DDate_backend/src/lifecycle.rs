@@ -0,0 +1,174 @@
+use candid::CandidType;
+use ic_cdk::{caller, update};
+use serde::{Deserialize, Serialize};
+
+use crate::errors::DDateError;
+use crate::guards::{authorize_user, is_admin, is_anonymous, is_moderator};
+use crate::profile_creation::UserProfileCreationInfo;
+use crate::state_handler::{mutate_state, State};
+
+#[derive(Debug, Serialize, Clone, Copy, Deserialize, CandidType, PartialEq, Eq)]
+pub enum AccountState {
+    Active,
+    // Hidden from discovery by the owner; existing matches can still chat.
+    Paused,
+    // Switched off by the owner, who can reactivate it.
+    Deactivated,
+    // Waiting out the grace period before the account is erased; the owner can cancel.
+    PendingDeletion,
+    // Locked by a moderator until lifted.
+    Suspended,
+    Banned,
+}
+
+// Who is asking for a transition; each transition names the actors allowed to make it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Actor {
+    Owner,
+    Moderator,
+    Admin,
+}
+
+impl AccountState {
+    // The owner can sign in and use the app: edit their profile, browse, swipe and chat.
+    pub fn is_usable(self) -> bool {
+        matches!(self, AccountState::Active | AccountState::Paused)
+    }
+
+    // Shown in other users' decks and open to new swipes and likes.
+    pub fn is_discoverable(self) -> bool {
+        self == AccountState::Active
+    }
+
+    // Locked by moderation; the owner cannot act on the account, not even to delete it.
+    pub fn is_locked(self) -> bool {
+        matches!(self, AccountState::Suspended | AccountState::Banned)
+    }
+
+    pub fn can_message(self) -> bool {
+        self.is_usable()
+    }
+
+    pub fn receives_notifications(self) -> bool {
+        self.is_usable()
+    }
+
+    pub fn can_transition(self, to: AccountState, actor: Actor) -> bool {
+        use AccountState::*;
+        let moderator = matches!(actor, Actor::Moderator | Actor::Admin);
+        match (self, to) {
+            (Active, Paused) | (Paused, Active) => actor == Actor::Owner,
+            (Active | Paused, Deactivated) => actor == Actor::Owner,
+            (Deactivated, Active) => actor == Actor::Owner,
+            (Active | Paused | Deactivated, PendingDeletion) => actor == Actor::Owner,
+            (PendingDeletion, Active) => actor == Actor::Owner,
+            (Active | Paused | Deactivated | PendingDeletion, Suspended) => moderator,
+            (Suspended, Active) => moderator,
+            (Active | Paused | Deactivated | PendingDeletion | Suspended, Banned) => actor == Actor::Admin,
+            (Banned, Active) => actor == Actor::Admin,
+            _ => false,
+        }
+    }
+}
+
+impl UserProfileCreationInfo {
+    // Moves the record to `to` without saving it, so callers can change other fields in the same write.
+    pub fn apply_transition(&mut self, to: AccountState, actor: Actor, now: u64) -> Result<AccountState, DDateError> {
+        let from = self.account_state;
+        if !from.can_transition(to, actor) {
+            return Err(DDateError::Conflict(format!(
+                "Account cannot move from {:?} to {:?} as {:?}",
+                from, to, actor
+            )));
+        }
+        self.account_state = to;
+        self.state_changed_at = now;
+        if to != AccountState::Paused {
+            self.paused_until = None;
+        }
+        if to != AccountState::PendingDeletion {
+            self.deletion_scheduled_at = None;
+        }
+        ic_cdk::println!("User ID {} moved from {:?} to {:?}", self.user_id, from, to);
+        Ok(from)
+    }
+}
+
+impl State {
+    pub fn transition_account(
+        &mut self,
+        user_id: &str,
+        to: AccountState,
+        actor: Actor,
+        now: u64,
+    ) -> Result<AccountState, DDateError> {
+        let mut profile = self
+            .user_profiles
+            .get(&user_id.to_string())
+            .ok_or_else(|| DDateError::NotFound(format!("User ID '{}' not found", user_id)))?;
+        let from = profile.apply_transition(to, actor, now)?;
+        self.save_profile(user_id.to_string(), profile);
+        Ok(from)
+    }
+}
+
+#[update(guard = "is_anonymous")]
+pub fn deactivate_account(user_id: String) -> Result<String, DDateError> {
+    mutate_state(|state| {
+        authorize_user(state, &user_id)?;
+        state.transition_account(&user_id, AccountState::Deactivated, Actor::Owner, ic_cdk::api::time())?;
+        Ok(format!("User ID {} has been deactivated.", user_id))
+    })
+}
+
+#[update(guard = "is_anonymous")]
+pub fn reactivate_account(user_id: String) -> Result<String, DDateError> {
+    mutate_state(|state| {
+        authorize_user(state, &user_id)?;
+        let profile = state
+            .user_profiles
+            .get(&user_id)
+            .ok_or_else(|| DDateError::NotFound(format!("User ID '{}' not found", user_id)))?;
+        if profile.account_state != AccountState::Deactivated {
+            return Err(DDateError::Conflict(format!("Only deactivated accounts can be reactivated; this one is {:?}", profile.account_state)));
+        }
+        state.transition_account(&user_id, AccountState::Active, Actor::Owner, ic_cdk::api::time())?;
+        Ok(format!("User ID {} has been reactivated.", user_id))
+    })
+}
+
+#[update(guard = "is_moderator")]
+pub fn suspend_account(user_id: String) -> Result<String, DDateError> {
+    ic_cdk::println!("Moderator {} suspending account with user_id: {}", caller(), user_id);
+    mutate_state(|state| {
+        state.transition_account(&user_id, AccountState::Suspended, Actor::Moderator, ic_cdk::api::time())?;
+        Ok(format!("User ID {} has been suspended.", user_id))
+    })
+}
+
+#[update(guard = "is_moderator")]
+pub fn lift_suspension(user_id: String) -> Result<String, DDateError> {
+    ic_cdk::println!("Moderator {} lifting suspension of user_id: {}", caller(), user_id);
+    mutate_state(|state| {
+        state.transition_account(&user_id, AccountState::Active, Actor::Moderator, ic_cdk::api::time())?;
+        Ok(format!("Suspension of user ID {} has been lifted.", user_id))
+    })
+}
+
+#[update(guard = "is_admin")]
+pub fn ban_account(user_id: String) -> Result<String, DDateError> {
+    ic_cdk::println!("Admin {} banning account with user_id: {}", caller(), user_id);
+    mutate_state(|state| {
+        state.transition_account(&user_id, AccountState::Banned, Actor::Admin, ic_cdk::api::time())?;
+        Ok(format!("User ID {} has been banned.", user_id))
+    })
+}
+
+#[update(guard = "is_admin")]
+pub fn unban_account(user_id: String) -> Result<String, DDateError> {
+    ic_cdk::println!("Admin {} unbanning account with user_id: {}", caller(), user_id);
+    mutate_state(|state| {
+        state.transition_account(&user_id, AccountState::Active, Actor::Admin, ic_cdk::api::time())?;
+        Ok(format!("User ID {} has been unbanned.", user_id))
+    })
+}
@@ -0,0 +1,555 @@
+use std::collections::{BTreeMap, HashSet, VecDeque};
+
+use candid::{CandidType, Decode, Encode, Principal};
+use ic_cdk::query;
+use ic_stable_structures::StableBTreeMap;
+use serde::{Deserialize, Serialize};
+
+use crate::attributes::{choice_from_legacy, legacy_choice_dropped, Diet, Drinking, Gender, GenderPronouns, Religion, Smoking, Zodiac};
+use crate::guards::is_admin;
+use crate::lifecycle::AccountState;
+use crate::privacy::PrivacySettings;
+use crate::profile_creation::{Notification, UserProfileCreationInfo, UserProfileParams};
+use crate::state_handler::{get_profiledata_memory, Memory, RawBytes, State};
+
+// Stored profiles start with this magic and a little-endian u16 version, followed by the candid payload.
+// Records written before versioning existed have no header and count as version 0.
+const PROFILE_MAGIC: &[u8; 4] = b"DDPV";
+const PROFILE_HEADER_LENGTH: usize = PROFILE_MAGIC.len() + 2;
+pub const CURRENT_PROFILE_VERSION: u16 = 4;
+
+// `UserProfileParams` up to version 2, when the categorical attributes were free-form text.
+#[derive(Deserialize, CandidType)]
+struct ParamsV2 {
+    user_id: Option<String>,
+    gender: Option<String>,
+    email: Option<String>,
+    name: Option<String>,
+    mobile_number: Option<String>,
+    dob: Option<String>,
+    gender_pronouns: Option<String>,
+    life_path_number: Option<String>,
+    religion: Option<String>,
+    zodiac: Option<String>,
+    diet: Option<String>,
+    smoking: Option<String>,
+    drinking: Option<String>,
+    hobbies: Option<Vec<String>>,
+    sports: Option<Vec<String>>,
+    interests_in: Option<String>,
+    min_preferred_age: Option<u64>,
+    max_preferred_age: Option<u64>,
+    location_city: Option<String>,
+    location_state: Option<String>,
+    location_country: Option<String>,
+    preferred_city: Option<String>,
+    preferred_state: Option<String>,
+    preferred_country: Option<String>,
+    introduction: Option<String>,
+    images: Option<Vec<String>>,
+    privacy: Option<PrivacySettings>,
+    age: Option<u64>,
+    likes: Option<HashSet<String>>,
+    matches: Option<Vec<String>>,
+    notifications: Option<VecDeque<Notification>>,
+    matched_profiles: Option<Vec<String>>,
+    leftswipes: Option<HashSet<String>>,
+    rightswipes: Option<HashSet<String>>,
+}
+
+impl From<ParamsV2> for UserProfileParams {
+    fn from(params: ParamsV2) -> Self {
+        UserProfileParams {
+            user_id: params.user_id,
+            gender: choice_from_legacy(params.gender),
+            email: params.email,
+            name: params.name,
+            mobile_number: params.mobile_number,
+            dob: params.dob,
+            gender_pronouns: choice_from_legacy(params.gender_pronouns),
+            life_path_number: params.life_path_number,
+            religion: choice_from_legacy(params.religion),
+            zodiac: choice_from_legacy(params.zodiac),
+            diet: choice_from_legacy(params.diet),
+            smoking: choice_from_legacy(params.smoking),
+            drinking: choice_from_legacy(params.drinking),
+            hobbies: params.hobbies,
+            sports: params.sports,
+            interests_in: choice_from_legacy(params.interests_in),
+            min_preferred_age: params.min_preferred_age,
+            max_preferred_age: params.max_preferred_age,
+            location_city: params.location_city,
+            location_state: params.location_state,
+            location_country: params.location_country,
+            preferred_city: params.preferred_city,
+            preferred_state: params.preferred_state,
+            preferred_country: params.preferred_country,
+            introduction: params.introduction,
+            images: params.images,
+            privacy: params.privacy,
+            // Added after version 2 as an optional record, so it needs no version bump.
+            extended: None,
+            age: params.age,
+            likes: params.likes,
+            matches: params.matches,
+            notifications: params.notifications,
+            matched_profiles: params.matched_profiles,
+            leftswipes: params.leftswipes,
+            rightswipes: params.rightswipes,
+        }
+    }
+}
+
+impl ParamsV2 {
+    // Categorical values that hold text but fit no variant, and so are lost by the conversion above.
+    fn dropped_choices(&self) -> u64 {
+        [
+            legacy_choice_dropped::<Gender>(&self.gender),
+            legacy_choice_dropped::<GenderPronouns>(&self.gender_pronouns),
+            legacy_choice_dropped::<Religion>(&self.religion),
+            legacy_choice_dropped::<Zodiac>(&self.zodiac),
+            legacy_choice_dropped::<Diet>(&self.diet),
+            legacy_choice_dropped::<Smoking>(&self.smoking),
+            legacy_choice_dropped::<Drinking>(&self.drinking),
+            legacy_choice_dropped::<Gender>(&self.interests_in),
+        ]
+        .into_iter()
+        .filter(|dropped| *dropped)
+        .count() as u64
+    }
+}
+
+// `expired` was set on anyone who had been swiped once and is dropped; discovery now skips profiles
+// the viewer has already swiped instead.
+fn legacy_account_state(status: bool) -> AccountState {
+    if status {
+        AccountState::Active
+    } else {
+        AccountState::Deactivated
+    }
+}
+
+// Layout of versions 0 and 1, before the edit version counter was added.
+#[derive(Deserialize, CandidType)]
+struct ProfileV1 {
+    user_id: String,
+    created_at: u64,
+    creator_principal: Principal,
+    params: ParamsV2,
+    notifications: VecDeque<Notification>,
+    matched_profiles: Vec<String>,
+    status: bool,
+}
+
+impl From<ProfileV1> for UserProfileCreationInfo {
+    fn from(profile: ProfileV1) -> Self {
+        UserProfileCreationInfo {
+            user_id: profile.user_id,
+            created_at: profile.created_at,
+            creator_principal: profile.creator_principal,
+            params: profile.params.into(),
+            notifications: profile.notifications,
+            matched_profiles: profile.matched_profiles,
+            account_state: legacy_account_state(profile.status),
+            state_changed_at: profile.created_at,
+            paused_until: None,
+            deletion_scheduled_at: None,
+            version: 0,
+        }
+    }
+}
+
+// Layout of version 2, before the categorical attributes became variants.
+#[derive(Deserialize, CandidType)]
+struct ProfileV2 {
+    user_id: String,
+    created_at: u64,
+    creator_principal: Principal,
+    params: ParamsV2,
+    notifications: VecDeque<Notification>,
+    matched_profiles: Vec<String>,
+    status: bool,
+    version: u64,
+}
+
+impl From<ProfileV2> for UserProfileCreationInfo {
+    fn from(profile: ProfileV2) -> Self {
+        UserProfileCreationInfo {
+            user_id: profile.user_id,
+            created_at: profile.created_at,
+            creator_principal: profile.creator_principal,
+            params: profile.params.into(),
+            notifications: profile.notifications,
+            matched_profiles: profile.matched_profiles,
+            account_state: legacy_account_state(profile.status),
+            state_changed_at: profile.created_at,
+            paused_until: None,
+            deletion_scheduled_at: None,
+            version: profile.version,
+        }
+    }
+}
+
+// Layout of version 3, before the `status` and `expired` flags became an account state.
+#[derive(Deserialize, CandidType)]
+struct ProfileV3 {
+    user_id: String,
+    created_at: u64,
+    creator_principal: Principal,
+    params: UserProfileParams,
+    notifications: VecDeque<Notification>,
+    matched_profiles: Vec<String>,
+    status: bool,
+    version: u64,
+}
+
+impl From<ProfileV3> for UserProfileCreationInfo {
+    fn from(profile: ProfileV3) -> Self {
+        UserProfileCreationInfo {
+            user_id: profile.user_id,
+            created_at: profile.created_at,
+            creator_principal: profile.creator_principal,
+            params: profile.params,
+            notifications: profile.notifications,
+            matched_profiles: profile.matched_profiles,
+            account_state: legacy_account_state(profile.status),
+            state_changed_at: profile.created_at,
+            paused_until: None,
+            deletion_scheduled_at: None,
+            version: profile.version,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Clone, Deserialize, CandidType)]
+pub struct VersionCount {
+    pub version: u16,
+    pub count: u64,
+}
+
+#[derive(Debug, Serialize, Clone, Deserialize, CandidType)]
+pub struct DecodeFailure {
+    pub user_id: String,
+    pub version: u16,
+    pub error: String,
+}
+
+#[derive(Debug, Serialize, Clone, Deserialize, CandidType)]
+pub struct MigrationReport {
+    pub current_version: u16,
+    pub total_records: u64,
+    pub versions: Vec<VersionCount>,
+    // Records that `post_upgrade` would rewrite to the current version.
+    pub pending_migration: u64,
+    pub decode_failures: Vec<DecodeFailure>,
+    // Categorical values in records from before version 3 that the migration would drop.
+    pub dropped_values: u64,
+}
+
+// Raw view of the profile map, used to look at record versions without decoding them.
+// Must not be kept around across writes to `State::user_profiles`, which shares its memory.
+fn raw_profiles() -> StableBTreeMap<String, RawBytes, Memory> {
+    StableBTreeMap::init(get_profiledata_memory())
+}
+
+pub fn encode_profile(profile: &UserProfileCreationInfo) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(PROFILE_HEADER_LENGTH);
+    bytes.extend_from_slice(PROFILE_MAGIC);
+    bytes.extend_from_slice(&CURRENT_PROFILE_VERSION.to_le_bytes());
+    bytes.extend(Encode!(profile).expect("encoding a profile should always succeed"));
+    bytes
+}
+
+fn split_header(bytes: &[u8]) -> (u16, &[u8]) {
+    if bytes.len() >= PROFILE_HEADER_LENGTH && bytes.starts_with(PROFILE_MAGIC) {
+        let version = u16::from_le_bytes([bytes[PROFILE_MAGIC.len()], bytes[PROFILE_MAGIC.len() + 1]]);
+        (version, &bytes[PROFILE_HEADER_LENGTH..])
+    } else {
+        (0, bytes)
+    }
+}
+
+// Categorical values the migration of `bytes` would drop; only records from before version 3 hold free text.
+fn dropped_choices(bytes: &[u8]) -> u64 {
+    let (version, payload) = split_header(bytes);
+    let params = match version {
+        0 | 1 => Decode!(payload, ProfileV1).ok().map(|profile| profile.params),
+        2 => Decode!(payload, ProfileV2).ok().map(|profile| profile.params),
+        _ => None,
+    };
+    params.map_or(0, |params| params.dropped_choices())
+}
+
+// Decodes a record of any known version and migrates it up to the current layout.
+// Each schema change adds a match arm that decodes the old layout and converts it.
+pub fn decode_profile(bytes: &[u8]) -> Result<UserProfileCreationInfo, String> {
+    let (version, payload) = split_header(bytes);
+    match version {
+        0 | 1 => Decode!(payload, ProfileV1)
+            .map(UserProfileCreationInfo::from)
+            .map_err(|e| e.to_string()),
+        2 => Decode!(payload, ProfileV2)
+            .map(UserProfileCreationInfo::from)
+            .map_err(|e| e.to_string()),
+        3 => Decode!(payload, ProfileV3)
+            .map(UserProfileCreationInfo::from)
+            .map_err(|e| e.to_string()),
+        4 => Decode!(payload, UserProfileCreationInfo).map_err(|e| e.to_string()),
+        unknown => Err(format!(
+            "Unknown profile version {} (current is {})",
+            unknown, CURRENT_PROFILE_VERSION
+        )),
+    }
+}
+
+impl State {
+    // Rewrites every record older than the current version. Nothing is written unless all records decode.
+    pub fn migrate_profiles(&mut self) -> Result<u64, Vec<DecodeFailure>> {
+        let mut migrated = Vec::new();
+        let mut failures = Vec::new();
+        let mut dropped = 0;
+        for (user_id, raw) in raw_profiles().iter() {
+            let (version, _) = split_header(&raw.0);
+            if version == CURRENT_PROFILE_VERSION {
+                continue;
+            }
+            dropped += dropped_choices(&raw.0);
+            match decode_profile(&raw.0) {
+                Ok(profile) => migrated.push((user_id, profile)),
+                Err(error) => failures.push(DecodeFailure { user_id, version, error }),
+            }
+        }
+        if !failures.is_empty() {
+            return Err(failures);
+        }
+
+        if dropped > 0 {
+            ic_cdk::println!("Dropped {} categorical values that fit no variant", dropped);
+        }
+        let count = migrated.len() as u64;
+        for (user_id, profile) in migrated {
+            self.user_profiles.insert(user_id, profile);
+        }
+        Ok(count)
+    }
+}
+
+pub fn profile_migration_report() -> MigrationReport {
+    let mut versions: BTreeMap<u16, u64> = BTreeMap::new();
+    let mut decode_failures = Vec::new();
+    let mut total_records = 0;
+    let mut dropped_values = 0;
+    for (user_id, raw) in raw_profiles().iter() {
+        total_records += 1;
+        dropped_values += dropped_choices(&raw.0);
+        let (version, _) = split_header(&raw.0);
+        *versions.entry(version).or_default() += 1;
+        if let Err(error) = decode_profile(&raw.0) {
+            decode_failures.push(DecodeFailure { user_id, version, error });
+        }
+    }
+
+    let pending_migration = versions
+        .iter()
+        .filter(|(version, _)| **version != CURRENT_PROFILE_VERSION)
+        .map(|(_, count)| count)
+        .sum();
+    MigrationReport {
+        current_version: CURRENT_PROFILE_VERSION,
+        total_records,
+        versions: versions
+            .into_iter()
+            .map(|(version, count)| VersionCount { version, count })
+            .collect(),
+        pending_migration,
+        decode_failures,
+        dropped_values,
+    }
+}
+
+// Dry run of the `post_upgrade` migration: reports stored versions and records that would fail to decode.
+#[query(guard = "is_admin")]
+pub fn get_profile_migration_report() -> MigrationReport {
+    profile_migration_report()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::attributes::{Drinking, Gender, Smoking, Zodiac};
+
+    fn legacy_params() -> ParamsV2 {
+        ParamsV2 {
+            user_id: Some("u1".to_string()),
+            gender: Some("male".to_string()),
+            email: Some("a@example.com".to_string()),
+            name: Some("Ann".to_string()),
+            mobile_number: None,
+            dob: Some("1990-01-01".to_string()),
+            gender_pronouns: None,
+            life_path_number: None,
+            religion: None,
+            zodiac: Some("Aquarius".to_string()),
+            diet: None,
+            smoking: Some("Sometimes".to_string()),
+            drinking: Some("Special Occasions".to_string()),
+            hobbies: Some(vec!["Reading".to_string()]),
+            sports: None,
+            interests_in: Some("All".to_string()),
+            min_preferred_age: Some(20),
+            max_preferred_age: Some(30),
+            location_city: None,
+            location_state: None,
+            location_country: None,
+            preferred_city: None,
+            preferred_state: None,
+            preferred_country: None,
+            introduction: None,
+            images: None,
+            privacy: None,
+            age: Some(34),
+            likes: None,
+            matches: None,
+            notifications: None,
+            matched_profiles: None,
+            leftswipes: None,
+            rightswipes: None,
+        }
+    }
+
+    fn with_header(version: u16, payload: Vec<u8>) -> Vec<u8> {
+        let mut bytes = PROFILE_MAGIC.to_vec();
+        bytes.extend_from_slice(&version.to_le_bytes());
+        bytes.extend(payload);
+        bytes
+    }
+
+    fn profile_v1(status: bool) -> ProfileV1 {
+        ProfileV1 {
+            user_id: "u1".to_string(),
+            created_at: 7,
+            creator_principal: Principal::anonymous(),
+            params: legacy_params(),
+            notifications: VecDeque::new(),
+            matched_profiles: vec!["u2".to_string()],
+            status,
+        }
+    }
+
+    fn assert_legacy_params(params: &UserProfileParams) {
+        assert_eq!(params.gender, Some(Gender::Male));
+        assert_eq!(params.zodiac, None);
+        assert_eq!(params.smoking, Some(Smoking::SocialSmoker));
+        assert_eq!(params.drinking, Some(Drinking::SpecialOccasions));
+        assert_eq!(params.interests_in, Some(Gender::Other("All".to_string())));
+        assert_eq!(params.name.as_deref(), Some("Ann"));
+        assert!(params.extended.is_none());
+    }
+
+    #[test]
+    fn decodes_unversioned_records_as_version_0() {
+        let bytes = Encode!(&profile_v1(true)).unwrap();
+        assert_eq!(split_header(&bytes).0, 0);
+
+        let profile = decode_profile(&bytes).unwrap();
+        assert_eq!(profile.user_id, "u1");
+        assert_eq!(profile.version, 0);
+        assert_eq!(profile.account_state, AccountState::Active);
+        assert_eq!(profile.state_changed_at, 7);
+        assert_eq!(profile.matched_profiles, vec!["u2".to_string()]);
+        assert_legacy_params(&profile.params);
+    }
+
+    #[test]
+    fn decodes_version_1_records() {
+        let bytes = with_header(1, Encode!(&profile_v1(false)).unwrap());
+        let profile = decode_profile(&bytes).unwrap();
+        assert_eq!(profile.version, 0);
+        assert_eq!(profile.account_state, AccountState::Deactivated);
+        assert_legacy_params(&profile.params);
+    }
+
+    #[test]
+    fn decodes_version_2_records_and_keeps_the_edit_version() {
+        let v1 = profile_v1(true);
+        let v2 = ProfileV2 {
+            user_id: v1.user_id,
+            created_at: v1.created_at,
+            creator_principal: v1.creator_principal,
+            params: v1.params,
+            notifications: v1.notifications,
+            matched_profiles: v1.matched_profiles,
+            status: v1.status,
+            version: 5,
+        };
+        let profile = decode_profile(&with_header(2, Encode!(&v2).unwrap())).unwrap();
+        assert_eq!(profile.version, 5);
+        assert_eq!(profile.account_state, AccountState::Active);
+        assert_legacy_params(&profile.params);
+    }
+
+    #[test]
+    fn decodes_version_3_records() {
+        let v3 = ProfileV3 {
+            user_id: "u3".to_string(),
+            created_at: 1,
+            creator_principal: Principal::anonymous(),
+            params: UserProfileParams {
+                zodiac: Some(Zodiac::Dragon),
+                ..UserProfileParams::default()
+            },
+            notifications: VecDeque::new(),
+            matched_profiles: Vec::new(),
+            status: false,
+            version: 9,
+        };
+        let profile = decode_profile(&with_header(3, Encode!(&v3).unwrap())).unwrap();
+        assert_eq!(profile.user_id, "u3");
+        assert_eq!(profile.version, 9);
+        assert_eq!(profile.account_state, AccountState::Deactivated);
+        assert_eq!(profile.params.zodiac, Some(Zodiac::Dragon));
+    }
+
+    #[test]
+    fn round_trips_the_current_version() {
+        let profile = UserProfileCreationInfo {
+            user_id: "u4".to_string(),
+            account_state: AccountState::Paused,
+            paused_until: Some(99),
+            version: 3,
+            ..UserProfileCreationInfo::default()
+        };
+        let bytes = encode_profile(&profile);
+        assert_eq!(split_header(&bytes).0, CURRENT_PROFILE_VERSION);
+
+        let decoded = decode_profile(&bytes).unwrap();
+        assert_eq!(encode_profile(&decoded), bytes);
+    }
+
+    #[test]
+    fn rejects_unknown_versions() {
+        let bytes = with_header(CURRENT_PROFILE_VERSION + 1, Vec::new());
+        assert!(decode_profile(&bytes).unwrap_err().contains("Unknown profile version"));
+    }
+
+    #[test]
+    fn treats_short_or_unmarked_bytes_as_unversioned() {
+        assert_eq!(split_header(b"DDPV"), (0, &b"DDPV"[..]));
+        assert_eq!(split_header(b"XXXX\x04\x00rest"), (0, &b"XXXX\x04\x00rest"[..]));
+        assert_eq!(split_header(b"DDPV\x02\x00rest"), (2, &b"rest"[..]));
+    }
+
+    #[test]
+    fn maps_the_legacy_status_flag() {
+        assert_eq!(legacy_account_state(true), AccountState::Active);
+        assert_eq!(legacy_account_state(false), AccountState::Deactivated);
+    }
+
+    #[test]
+    fn counts_choices_the_migration_drops() {
+        let bytes = Encode!(&profile_v1(true)).unwrap();
+        // Only the unknown zodiac "Aquarius" is lost; "Sometimes" maps to a smoking variant.
+        assert_eq!(dropped_choices(&bytes), 1);
+        assert_eq!(dropped_choices(&encode_profile(&UserProfileCreationInfo::default())), 0);
+    }
+}
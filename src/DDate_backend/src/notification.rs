@@ -1,17 +1,40 @@
 
 
+use candid::Principal;
+
+use crate::errors::DDateError;
 use crate::profile_creation::Notification;
 use crate::profile_creation::NotificationType;
-use crate::state_handler::State;
+use crate::profile_creation::UserProfileCreationInfo;
+use crate::state_handler::{Candid, State};
 
 impl State {
-    pub fn send_like_notification(&mut self, sender_id: String, receiver_id: String) -> Result<(), String> {
-        const MAX_NOTIFICATIONS: usize = 100;
+    // Appends a notification, dropping the oldest ones once the configured cap is reached.
+    pub fn push_notification(&self, profile: &mut UserProfileCreationInfo, notification: Notification) {
+        let max_notifications = self.config().max_notifications as usize;
+        while profile.notifications.len() >= max_notifications {
+            profile.notifications.pop_front();
+        }
+        profile.notifications.push_back(notification);
+    }
+
+    // Notices addressed to a principal rather than a profile, for principals that may no longer own one.
+    pub fn push_principal_notice(&mut self, principal: Principal, notification: Notification) {
+        let max_notifications = self.config().max_notifications as usize;
+        let mut notices = self.principal_notices.get(&principal).map(|notices| notices.0).unwrap_or_default();
+        while notices.len() >= max_notifications {
+            notices.pop_front();
+        }
+        notices.push_back(notification);
+        self.principal_notices.insert(principal, Candid(notices));
+    }
 
+    pub fn send_like_notification(&mut self, sender_id: String, receiver_id: String) -> Result<(), DDateError> {
         let like_notification = Notification {
             sender_id: sender_id.clone(),
             receiver_id: receiver_id.clone(),
             notification_type: NotificationType::Like,
+            old_principal: None,
         };
 
         ic_cdk::println!("Profiles available before sending notification: {:?}", self.user_profiles.iter().map(|(k, _)| k).collect::<Vec<_>>());
@@ -19,38 +42,33 @@ impl State {
         // Check if sender profile exists
         let sender_profile = match self.user_profiles.get(&sender_id) {
             Some(profile) => profile,
-            None => return Err(format!("Sender ID {} does not exist.", sender_id)),
+            None => return Err(DDateError::NotFound(format!("Sender ID {} does not exist.", sender_id))),
         };
 
         // Check if receiver profile exists
-        let receiver_profile = match self.user_profiles.remove(&receiver_id) {
+        let receiver_profile = match self.user_profiles.get(&receiver_id) {
             Some(profile) => profile,
             None => {
                 ic_cdk::println!("Receiver profile not found: {}", receiver_id);
-                return Err(format!("Receiver ID {} does not exist.", receiver_id));
+                return Err(DDateError::NotFound(format!("Receiver ID {} does not exist.", receiver_id)));
             },
         };
 
-        // Handle receiver profile after removal
         let mut receiver_profile = receiver_profile;
 
-        if !sender_profile.status {
-            return Err("Sender's account is inactive".to_string());
-        }
-
-        if !receiver_profile.status {
-            return Err("Receiver's account is inactive".to_string());
+        if !sender_profile.account_state.is_usable() {
+            return Err(DDateError::Inactive("Sender's account is inactive".to_string()));
         }
 
-        if receiver_profile.notifications.len() >= MAX_NOTIFICATIONS {
-            receiver_profile.notifications.pop_front();
+        if !receiver_profile.account_state.receives_notifications() {
+            return Err(DDateError::Inactive("Receiver's account is inactive".to_string()));
         }
 
-        receiver_profile.notifications.push_back(like_notification.clone());
+        self.push_notification(&mut receiver_profile, like_notification.clone());
         ic_cdk::println!("Notification sent: {:?}", like_notification);
 
-        // Insert modified receiver profile back into the map
-        self.user_profiles.insert(receiver_id.clone(), receiver_profile);
+        // Write the modified receiver profile back into the map
+        self.save_profile(receiver_id.clone(), receiver_profile);
 
         Ok(())
     }
@@ -60,18 +78,18 @@ impl State {
 
 
 
-pub fn get_notifications(state: &State, user_id: String) -> Result<Vec<Notification>, String> {
+pub fn get_notifications(state: &State, user_id: String) -> Result<Vec<Notification>, DDateError> {
     // Access the state to retrieve notifications
     if let Some(profile) = state.user_profiles.get(&user_id) {
-        if !profile.status {
-            return Err("User's account is inactive".to_string());
+        if !profile.account_state.is_usable() {
+            return Err(DDateError::Inactive("User's account is inactive".to_string()));
         }
         // Retrieve notifications if user exists
         let notifications = profile.notifications.iter().cloned().collect();
         Ok(notifications)
     } else {
         // Return an error if user does not exist
-        Err(format!("User ID '{}' does not exist", user_id))
+        Err(DDateError::NotFound(format!("User ID '{}' does not exist", user_id)))
     }
 }
 
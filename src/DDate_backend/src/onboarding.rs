@@ -0,0 +1,148 @@
+use std::time::Duration;
+
+use candid::{CandidType, Principal};
+use ic_cdk::{caller, query, update};
+use serde::{Deserialize, Serialize};
+
+use crate::errors::DDateError;
+use crate::guards::is_anonymous;
+use crate::profile_creation::{generate_user_id, new_profile_info, UserInputParams, UserProfileParams};
+use crate::state_handler::{mutate_state, read_state, Candid, State};
+use crate::validation::{validate_completed_draft, validate_onboarding_step};
+
+// Drafts untouched for a week are treated as abandoned.
+const DRAFT_TTL_NANOS: u64 = 7 * 24 * 60 * 60 * 1_000_000_000;
+const DRAFT_PURGE_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+// One variant per sign-up form, in the order the frontend shows them.
+#[derive(Debug, Serialize, Clone, Copy, Deserialize, CandidType, PartialEq, Eq)]
+pub enum OnboardingStep {
+    Basics,
+    About,
+    Lifestyle,
+    Interests,
+    Preferences,
+    Photos,
+}
+
+#[derive(Debug, Serialize, Clone, Deserialize, CandidType)]
+pub struct OnboardingDraft {
+    pub principal: Principal,
+    // Everything saved so far, merged step by step.
+    pub params: UserProfileParams,
+    pub completed_steps: Vec<OnboardingStep>,
+    pub created_at: u64,
+    pub updated_at: u64,
+}
+
+impl State {
+    pub fn save_onboarding_step(
+        &mut self,
+        principal: Principal,
+        step: OnboardingStep,
+        params: UserInputParams,
+        now: u64,
+    ) -> Result<OnboardingDraft, DDateError> {
+        if let Some(existing_id) = self.find_user_id_by_principal(&principal) {
+            return Err(DDateError::Conflict(format!("Principal {} already has an active account with id: {}", principal, existing_id)));
+        }
+        let mut draft = self
+            .onboarding_drafts
+            .get(&principal)
+            .map(|draft| draft.0)
+            .unwrap_or_else(|| OnboardingDraft {
+                principal,
+                params: UserProfileParams::default(),
+                completed_steps: Vec::new(),
+                created_at: now,
+                updated_at: now,
+            });
+        validate_onboarding_step(step, &draft.params, &params, self.age_policy(now))?;
+        draft.params.merge(params.into());
+        if !draft.completed_steps.contains(&step) {
+            draft.completed_steps.push(step);
+        }
+        draft.updated_at = now;
+        self.onboarding_drafts.insert(principal, Candid(draft.clone()));
+        Ok(draft)
+    }
+
+    pub fn onboarding_draft(&self, principal: &Principal) -> Result<OnboardingDraft, DDateError> {
+        self.onboarding_drafts
+            .get(principal)
+            .map(|draft| draft.0)
+            .ok_or_else(|| DDateError::NotFound(format!("No onboarding draft found for principal ID: {}", principal)))
+    }
+
+    pub fn discard_onboarding_draft(&mut self, principal: &Principal) -> Result<String, DDateError> {
+        match self.onboarding_drafts.remove(principal) {
+            Some(_) => Ok(format!("Discarded onboarding draft for principal ID: {}", principal)),
+            None => Err(DDateError::NotFound(format!("No onboarding draft found for principal ID: {}", principal))),
+        }
+    }
+
+    // Turns the caller's draft into an account and removes the draft in the same message, so either both happen or neither does.
+    pub fn finalize_onboarding(&mut self, principal: Principal, user_id: String, now: u64) -> Result<String, DDateError> {
+        let draft = self.onboarding_draft(&principal)?;
+        validate_completed_draft(&draft.params, self.age_policy(now))?;
+
+        let profile_info = new_profile_info(user_id.clone(), principal, draft.params);
+        let result = self.create_account(user_id, profile_info)?;
+        self.onboarding_drafts.remove(&principal);
+        Ok(result)
+    }
+
+    pub fn purge_abandoned_drafts(&mut self, now: u64) -> u64 {
+        let abandoned: Vec<Principal> = self
+            .onboarding_drafts
+            .iter()
+            .filter(|(_, draft)| now.saturating_sub(draft.updated_at) > DRAFT_TTL_NANOS)
+            .map(|(principal, _)| principal)
+            .collect();
+        for principal in &abandoned {
+            self.onboarding_drafts.remove(principal);
+        }
+        abandoned.len() as u64
+    }
+}
+
+fn purge_abandoned_drafts() {
+    let purged = mutate_state(|state| state.purge_abandoned_drafts(ic_cdk::api::time()));
+    if purged > 0 {
+        ic_cdk::println!("Purged {} abandoned onboarding drafts", purged);
+    }
+}
+
+pub fn start_draft_purge_timer() {
+    ic_cdk_timers::set_timer_interval(DRAFT_PURGE_INTERVAL, purge_abandoned_drafts);
+}
+
+#[update(guard = "is_anonymous")]
+pub fn save_onboarding_step(step: OnboardingStep, params: UserInputParams) -> Result<OnboardingDraft, DDateError> {
+    let principal = caller();
+    mutate_state(|state| state.save_onboarding_step(principal, step, params, ic_cdk::api::time()))
+}
+
+#[query(guard = "is_anonymous")]
+pub fn get_onboarding_draft() -> Result<OnboardingDraft, DDateError> {
+    read_state(|state| state.onboarding_draft(&caller()))
+}
+
+#[update(guard = "is_anonymous")]
+pub fn discard_onboarding_draft() -> Result<String, DDateError> {
+    mutate_state(|state| state.discard_onboarding_draft(&caller()))
+}
+
+#[update(guard = "is_anonymous")]
+pub async fn finalize_onboarding() -> Result<String, DDateError> {
+    let principal = caller();
+    // Fail before spending a raw_rand call when there is nothing to finalize.
+    read_state(|state| {
+        let draft = state.onboarding_draft(&principal)?;
+        validate_completed_draft(&draft.params, state.age_policy(ic_cdk::api::time())).map_err(DDateError::from)
+    })?;
+
+    let user_id = generate_user_id().await?;
+    ic_cdk::println!("Finalizing onboarding for principal {} as user_id: {}", principal, user_id);
+    mutate_state(|state| state.finalize_onboarding(principal, user_id, ic_cdk::api::time()))
+}
@@ -0,0 +1,93 @@
+use std::time::Duration;
+
+use ic_cdk::update;
+
+use crate::errors::DDateError;
+use crate::guards::{authorize_user, is_anonymous};
+use crate::lifecycle::{AccountState, Actor};
+use crate::state_handler::{mutate_state, read_state, State};
+
+impl State {
+    // Pauses an active account, or moves the end of a running pause. `until` of `None` pauses until the
+    // owner resumes by hand.
+    pub fn pause_account(&mut self, user_id: &str, until: Option<u64>, now: u64) -> Result<(), DDateError> {
+        if until.is_some_and(|until| until <= now) {
+            return Err(DDateError::InvalidInput("Pause end time must be in the future".to_string()));
+        }
+        let mut profile = self
+            .user_profiles
+            .get(&user_id.to_string())
+            .ok_or_else(|| DDateError::NotFound(format!("User ID '{}' not found", user_id)))?;
+        if profile.account_state != AccountState::Paused {
+            profile.apply_transition(AccountState::Paused, Actor::Owner, now)?;
+        }
+        profile.paused_until = until;
+        self.save_profile(user_id.to_string(), profile);
+        Ok(())
+    }
+
+    // Lifts the pause of `user_id` if its end time has passed. A stale timer left over from a pause that
+    // was resumed or moved by hand finds nothing to do.
+    pub fn end_expired_pause(&mut self, user_id: &str, now: u64) -> bool {
+        let expired = self.user_profiles.get(&user_id.to_string()).is_some_and(|profile| {
+            profile.account_state == AccountState::Paused && profile.paused_until.is_some_and(|until| until <= now)
+        });
+        expired && self.transition_account(user_id, AccountState::Active, Actor::Owner, now).is_ok()
+    }
+
+    fn scheduled_pauses(&self) -> Vec<(String, u64)> {
+        self.user_profiles
+            .iter()
+            .filter(|(_, profile)| profile.account_state == AccountState::Paused)
+            .filter_map(|(user_id, profile)| profile.paused_until.map(|until| (user_id, until)))
+            .collect()
+    }
+}
+
+fn schedule_pause_end(user_id: String, until: u64) {
+    let delay = Duration::from_nanos(until.saturating_sub(ic_cdk::api::time()));
+    ic_cdk_timers::set_timer(delay, move || {
+        if mutate_state(|state| state.end_expired_pause(&user_id, ic_cdk::api::time())) {
+            ic_cdk::println!("Pause of user ID {} has ended", user_id);
+        }
+    });
+}
+
+// Re-arms the timers of every pause with an end time, since timers do not survive upgrades.
+pub fn start_pause_timers() {
+    for (user_id, until) in read_state(|state| state.scheduled_pauses()) {
+        schedule_pause_end(user_id, until);
+    }
+}
+
+// Hides the profile from discovery and new swipes; existing matches can keep chatting.
+#[update(guard = "is_anonymous")]
+pub fn pause_account(user_id: String, until: Option<u64>) -> Result<String, DDateError> {
+    mutate_state(|state| {
+        authorize_user(state, &user_id)?;
+        state.pause_account(&user_id, until, ic_cdk::api::time())
+    })?;
+    match until {
+        Some(until) => {
+            schedule_pause_end(user_id.clone(), until);
+            Ok(format!("User ID {} is paused until {}.", user_id, until))
+        }
+        None => Ok(format!("User ID {} is paused.", user_id)),
+    }
+}
+
+#[update(guard = "is_anonymous")]
+pub fn resume_account(user_id: String) -> Result<String, DDateError> {
+    mutate_state(|state| {
+        authorize_user(state, &user_id)?;
+        let profile = state
+            .user_profiles
+            .get(&user_id)
+            .ok_or_else(|| DDateError::NotFound(format!("User ID '{}' not found", user_id)))?;
+        if profile.account_state != AccountState::Paused {
+            return Err(DDateError::Conflict(format!("Only paused accounts can be resumed; this one is {:?}", profile.account_state)));
+        }
+        state.transition_account(&user_id, AccountState::Active, Actor::Owner, ic_cdk::api::time())?;
+        Ok(format!("User ID {} has been resumed.", user_id))
+    })
+}
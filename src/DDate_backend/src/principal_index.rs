@@ -0,0 +1,69 @@
+use candid::Principal;
+
+use crate::errors::DDateError;
+use crate::state_handler::State;
+
+impl State {
+    // Resolves a principal to the user_id of its profile through the stable index. Accounts stay indexed in
+    // every lifecycle state, so a deactivated owner can still sign in to reactivate and a banned one cannot
+    // register again.
+    pub fn find_user_id_by_principal(&self, principal: &Principal) -> Option<String> {
+        let user_id = self.principal_index.get(principal)?;
+        self.user_profiles.contains_key(&user_id).then_some(user_id)
+    }
+
+    // Records `principal` as the owner of `user_id`, allowing one account per principal.
+    pub fn index_principal(&mut self, principal: Principal, user_id: String) -> Result<(), DDateError> {
+        if let Some(existing_id) = self.find_user_id_by_principal(&principal) {
+            if existing_id != user_id {
+                return Err(DDateError::Conflict(format!(
+                    "Principal {} already has an active account with id: {}",
+                    principal, existing_id
+                )));
+            }
+        }
+        self.principal_index.insert(principal, user_id);
+        Ok(())
+    }
+
+    // Drops the index entry for `principal` if it still points at `user_id`.
+    pub fn unindex_principal(&mut self, principal: &Principal, user_id: &str) {
+        if self.principal_index.get(principal).as_deref() == Some(user_id) {
+            self.principal_index.remove(principal);
+        }
+    }
+
+    // Drops the index entries of the creator and every linked principal of `user_id`.
+    pub fn unindex_user(&mut self, user_id: &str) {
+        if let Some(profile) = self.user_profiles.get(&user_id.to_string()) {
+            self.unindex_principal(&profile.creator_principal, user_id);
+        }
+        for linked in self.linked_principals_for(user_id) {
+            self.unindex_principal(&linked.principal, user_id);
+        }
+    }
+
+    // Rebuilds the index from the stored profiles, e.g. after upgrading a canister that predates it.
+    pub fn rebuild_principal_index(&mut self) {
+        let mut entries: Vec<(Principal, String)> = Vec::new();
+        for (user_id, profile) in self.user_profiles.iter() {
+            entries.push((profile.creator_principal, user_id.clone()));
+            for linked in self.linked_principals_for(&user_id) {
+                entries.push((linked.principal, user_id.clone()));
+            }
+        }
+
+        let stale: Vec<Principal> = self.principal_index.iter().map(|(principal, _)| principal).collect();
+        for principal in stale {
+            self.principal_index.remove(&principal);
+        }
+
+        for (principal, user_id) in entries {
+            // Keep the first profile per principal, in any lifecycle state, if older data holds duplicates.
+            if !self.principal_index.contains_key(&principal) {
+                self.principal_index.insert(principal, user_id);
+            }
+        }
+        ic_cdk::println!("Principal index rebuilt with {} entries", self.principal_index.len());
+    }
+}
@@ -0,0 +1,94 @@
+use candid::CandidType;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Serialize, Clone, Copy, Deserialize, CandidType, PartialEq, Eq)]
+pub enum Visibility {
+    Everyone,
+    MatchesOnly,
+    Nobody,
+}
+
+// Who a profile is being shown to, other than its owner.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Audience {
+    Match,
+    Everyone,
+}
+
+impl Visibility {
+    pub fn allows(self, audience: Audience) -> bool {
+        match self {
+            Visibility::Everyone => true,
+            Visibility::MatchesOnly => audience == Audience::Match,
+            Visibility::Nobody => false,
+        }
+    }
+}
+
+// Per-field visibility chosen by the owner; fields left as `None` use the defaults below.
+#[derive(Debug, Serialize, Clone, Default, Deserialize, CandidType)]
+pub struct PrivacySettings {
+    pub mobile_number: Option<Visibility>,
+    pub email: Option<Visibility>,
+    pub dob: Option<Visibility>,
+    pub religion: Option<Visibility>,
+    pub location_city: Option<Visibility>,
+    pub images: Option<Visibility>,
+}
+
+impl PrivacySettings {
+    // Contact details stay between matches unless the owner opts in.
+    pub fn mobile_number(&self) -> Visibility {
+        self.mobile_number.unwrap_or(Visibility::MatchesOnly)
+    }
+
+    pub fn email(&self) -> Visibility {
+        self.email.unwrap_or(Visibility::MatchesOnly)
+    }
+
+    pub fn dob(&self) -> Visibility {
+        self.dob.unwrap_or(Visibility::MatchesOnly)
+    }
+
+    pub fn religion(&self) -> Visibility {
+        self.religion.unwrap_or(Visibility::Everyone)
+    }
+
+    pub fn location_city(&self) -> Visibility {
+        self.location_city.unwrap_or(Visibility::Everyone)
+    }
+
+    pub fn images(&self) -> Visibility {
+        self.images.unwrap_or(Visibility::Everyone)
+    }
+
+    pub fn merge(&mut self, other: PrivacySettings) {
+        if let Some(mobile_number) = other.mobile_number {
+            self.mobile_number = Some(mobile_number);
+        }
+        if let Some(email) = other.email {
+            self.email = Some(email);
+        }
+        if let Some(dob) = other.dob {
+            self.dob = Some(dob);
+        }
+        if let Some(religion) = other.religion {
+            self.religion = Some(religion);
+        }
+        if let Some(location_city) = other.location_city {
+            self.location_city = Some(location_city);
+        }
+        if let Some(images) = other.images {
+            self.images = Some(images);
+        }
+    }
+}
+
+// Returns `value` only if `visibility` lets `audience` see it.
+pub fn visible_to<T: Clone>(value: &Option<T>, visibility: Visibility, audience: Audience) -> Option<T> {
+    if visibility.allows(audience) {
+        value.clone()
+    } else {
+        None
+    }
+}
@@ -1,20 +1,38 @@
 use candid::{CandidType, Principal};
 use ic_cdk::api::management_canister::main::raw_rand;
-use ic_cdk::{init, query, update};
+use ic_cdk::{caller, init, post_upgrade, query, update};
 use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
 use std::collections::{HashSet, VecDeque};
 
-// use crate::is_anonymous;
-
+use crate::age::{age_from_dob, Date};
+use crate::attributes::{Choice, Diet, Drinking, Gender, GenderPronouns, Religion, Smoking, Zodiac};
+use crate::audit::{diff_profiles, AuditEntry};
+use crate::cascade::schedule_deletion_tick;
+use crate::completeness::{completeness, IncompleteProfiles};
+use crate::config::CanisterArgs;
+use crate::extended_profile::{ExtendedProfile, ExtendedProfileFilter};
+use crate::guards::{authorize_user, caller_user_id, is_anonymous};
+use crate::lifecycle::AccountState;
+use crate::errors::DDateError;
+use crate::migrations::{encode_profile, CURRENT_PROFILE_VERSION};
+use crate::privacy::PrivacySettings;
+use crate::profile_views::{audience_for, ProfileView, PublicProfile};
+use crate::timers::start_timers;
+use crate::upgrade::fingerprint_state;
+use crate::validation::{validate_new_profile, validate_profile_update};
 use crate::{post_file_contents, state_handler};
-use crate::state_handler::{init_file_contents, mutate_state, read_state, State, STATE};
+use crate::state_handler::{
+    audit_key_contents, audit_log_contents, config_contents, data_exports_contents, deletion_jobs_contents, principal_notices_contents, onboarding_drafts_contents, init_file_contents, link_codes_contents, linked_principals_contents, mutate_state,
+    legacy_audit_log_contents, principal_index_contents, read_state, recovery_records_contents, roles_contents, swipe_quotas_contents,
+    upgrade_record_contents, State, STATE,
+};
 use crate::state_handler::Candid;
 
 #[derive(Debug, Serialize, Deserialize, CandidType)]
 pub struct PaginatedProfiles {
     pub total_profiles: usize,
-    pub profiles: Vec<UserProfileCreationInfo>,
+    pub profiles: Vec<PublicProfile>,
 }
 
 // Define the Notification struct
@@ -23,31 +41,34 @@ pub struct Notification {
     pub sender_id: String,
     pub receiver_id: String,
     pub notification_type: NotificationType,
+    // Set on `AccountRecovered`: the principal that lost access to the account.
+    pub old_principal: Option<Principal>,
 }
 
 #[derive(Debug, Serialize, Clone, Deserialize, CandidType)]
 pub enum NotificationType {
     Like,
+    AccountRecovered,
     // Add other notification types as needed
 }
 
 #[derive(Default, Clone, Deserialize, CandidType, Debug, Serialize)]
 pub struct UserInputParams {
-    pub gender: Option<String>,
+    pub gender: Option<Gender>,
     pub email: Option<String>,
     pub name: Option<String>,
     pub mobile_number: Option<String>,
     pub dob: Option<String>,
-    pub gender_pronouns: Option<String>,
+    pub gender_pronouns: Option<GenderPronouns>,
     pub life_path_number: Option<String>,
-    pub religion: Option<String>,
-    pub zodiac: Option<String>,
-    pub diet: Option<String>,
-    pub smoking: Option<String>,
-    pub drinking: Option<String>,
+    pub religion: Option<Religion>,
+    pub zodiac: Option<Zodiac>,
+    pub diet: Option<Diet>,
+    pub smoking: Option<Smoking>,
+    pub drinking: Option<Drinking>,
     pub hobbies: Option<Vec<String>>,
     pub sports: Option<Vec<String>>,
-    pub interests_in: Option<String>,
+    pub interests_in: Option<Gender>,
     pub min_preferred_age: Option<u64>,
     pub max_preferred_age: Option<u64>,
     pub location_city: Option<String>,
@@ -58,17 +79,12 @@ pub struct UserInputParams {
     pub preferred_country: Option<String>,
     pub introduction: Option<String>,
     pub images: Option<Vec<String>>,
+    pub privacy: Option<PrivacySettings>,
+    pub extended: Option<ExtendedProfile>,
+    // Ignored; the server derives `age` from `dob`.
     pub age: Option<u64>,
     // pub preferred_gender: Option<String>,
     // pub preferred_location: Option<String>,
-    // pub height: Option<String>,
-    // pub art_and_culture: Option<Vec<String>>,
-    // pub pets: Option<String>,
-    // pub general_habits: Option<Vec<String>>,
-    // pub outdoor_activities: Option<Vec<String>>,
-    // pub travel: Option<Vec<String>>,
-    // pub movies: Option<Vec<String>>,
-    // pub looking_for: Option<String>,
 
 }
 
@@ -80,8 +96,15 @@ pub struct UserProfileCreationInfo {
     pub params: UserProfileParams,
     pub notifications: VecDeque<Notification>,
     pub matched_profiles: Vec<String>,
-    pub status: bool,
-    pub expired: bool,
+    pub account_state: AccountState,
+    pub state_changed_at: u64,
+    // When a pause with an end time set by `pause_account` lifts on its own.
+    pub paused_until: Option<u64>,
+    // When a pending deletion requested with `request_account_deletion` goes ahead.
+    pub deletion_scheduled_at: Option<u64>,
+    // Bumped by `State::save_profile` when a field the owner edits changes; edits must name the version they
+    // were based on. Swipes, likes, matches and lifecycle moves leave it alone.
+    pub version: u64,
 }
 
 
@@ -94,8 +117,24 @@ impl Default for UserProfileCreationInfo {
             params: UserProfileParams::default(),
             notifications: VecDeque::new(),
             matched_profiles: Vec::new(),
-            status: true,
-            expired: false, 
+            account_state: AccountState::Active,
+            state_changed_at: 0,
+            paused_until: None,
+            deletion_scheduled_at: None,
+            version: 0,
+        }
+    }
+}
+
+impl UserProfileCreationInfo {
+    pub fn expect_version(&self, expected_version: u64) -> Result<(), DDateError> {
+        if self.version == expected_version {
+            Ok(())
+        } else {
+            Err(DDateError::VersionConflict {
+                expected_version,
+                current_version: self.version,
+            })
         }
     }
 }
@@ -103,21 +142,21 @@ impl Default for UserProfileCreationInfo {
 #[derive(Default, Clone, Deserialize, CandidType, Debug, Serialize)]
 pub struct UserProfileParams {
     pub user_id: Option<String>,
-    pub gender: Option<String>,
+    pub gender: Option<Gender>,
     pub email: Option<String>,
     pub name: Option<String>,
     pub mobile_number: Option<String>,
     pub dob: Option<String>,
-    pub gender_pronouns: Option<String>,
+    pub gender_pronouns: Option<GenderPronouns>,
     pub life_path_number: Option<String>,
-    pub religion: Option<String>,
-    pub zodiac: Option<String>,
-    pub diet: Option<String>,
-    pub smoking: Option<String>,
-    pub drinking: Option<String>,
+    pub religion: Option<Religion>,
+    pub zodiac: Option<Zodiac>,
+    pub diet: Option<Diet>,
+    pub smoking: Option<Smoking>,
+    pub drinking: Option<Drinking>,
     pub hobbies: Option<Vec<String>>,
     pub sports: Option<Vec<String>>,
-    pub interests_in: Option<String>,
+    pub interests_in: Option<Gender>,
     pub min_preferred_age: Option<u64>,
     pub max_preferred_age: Option<u64>,
     pub location_city: Option<String>,
@@ -128,17 +167,11 @@ pub struct UserProfileParams {
     pub preferred_country: Option<String>,
     pub introduction: Option<String>,
     pub images: Option<Vec<String>>,
+    pub privacy: Option<PrivacySettings>,
+    pub extended: Option<ExtendedProfile>,
     pub age: Option<u64>,
     // pub preferred_gender: Option<String>,
     // pub preferred_location: Option<String>,
-    // pub height: Option<String>,
-    // pub art_and_culture: Option<Vec<String>>,
-    // pub pets: Option<String>,
-    // pub general_habits: Option<Vec<String>>,
-    // pub outdoor_activities: Option<Vec<String>>,
-    // pub travel: Option<Vec<String>>,
-    // pub movies: Option<Vec<String>>,
-    // pub looking_for: Option<String>,
     pub likes: Option<HashSet<String>>,
     pub matches: Option<Vec<String>>,
     pub notifications: Option<VecDeque<Notification>>,
@@ -162,6 +195,7 @@ pub struct Message {
     pub timestamp: u64,
 }
 
+#[allow(dead_code)]
 #[derive(Debug, Serialize, Clone, Deserialize, CandidType)]
 pub struct UserChatList {
     pub name: UserProfileParams,
@@ -173,17 +207,110 @@ pub struct UserChatList {
 
 
 #[init]
-fn init() {
+fn init(args: Option<CanisterArgs>) {
     STATE.with(|state| {
         let mut state = state.borrow_mut();
         state.user_profiles = init_file_contents();
         state.user_messages = post_file_contents();
+        state.principal_index = principal_index_contents();
+        state.linked_principals = linked_principals_contents();
+        state.link_codes = link_codes_contents();
+        state.recovery_records = recovery_records_contents();
+        state.roles = roles_contents();
+        state.config = config_contents();
+        state.swipe_quotas = swipe_quotas_contents();
+        state.upgrade_record = upgrade_record_contents();
+        state.legacy_audit_log = legacy_audit_log_contents();
+        state.onboarding_drafts = onboarding_drafts_contents();
+        state.deletion_jobs = deletion_jobs_contents();
+        state.data_exports = data_exports_contents();
+        state.principal_notices = principal_notices_contents();
+        state.audit_log = audit_log_contents();
+        state.audit_key = audit_key_contents();
     });
+    apply_canister_args(args);
+    start_timers();
 }
 
+#[post_upgrade]
+fn post_upgrade(args: Option<CanisterArgs>) {
+    // `Init` replaces the whole config with its defaults, which would silently undo every tuned limit.
+    if let Some(CanisterArgs::Init(_)) = args {
+        ic_cdk::trap("post_upgrade only accepts Upgrade arguments; use Upgrade with a ConfigUpdate to change the config");
+    }
+    // Checked before anything below rewrites stable memory.
+    let verification = mutate_state(|state| {
+        let now = ic_cdk::api::time();
+        let after = fingerprint_state(state, now);
+        state.verify_upgrade(after, now)
+    });
+    ic_cdk::println!(
+        "Upgrade integrity check: {:?}, {} mismatched memories",
+        verification.result,
+        verification.mismatches.len()
+    );
+
+    // Trapping rolls the upgrade back, so an undecodable record never reaches the new code.
+    match mutate_state(|state| state.migrate_profiles()) {
+        Ok(migrated) => ic_cdk::println!("Migrated {} profiles to version {}", migrated, CURRENT_PROFILE_VERSION),
+        Err(failures) => ic_cdk::trap(&format!("Profile migration failed for {} records: {:?}", failures.len(), failures)),
+    }
+
+    // Canisters deployed before the principal index existed start with it empty.
+    mutate_state(|state| {
+        if state.principal_index.is_empty() && !state.user_profiles.is_empty() {
+            state.rebuild_principal_index();
+        }
+    });
+    let moved = mutate_state(|state| state.migrate_legacy_audit_log());
+    if moved > 0 {
+        ic_cdk::println!("Moved {} audit entries to the per-entry log", moved);
+    }
+    apply_canister_args(args);
+    start_timers();
+}
+
+fn apply_canister_args(args: Option<CanisterArgs>) {
+    if let Some(args) = args {
+        let installer = caller();
+        mutate_state(|state| state.apply_canister_args(args, installer, ic_cdk::api::time()))
+            .unwrap_or_else(|e| ic_cdk::trap(&format!("Invalid canister arguments: {}", e)));
+    }
+}
+
+// Audited fields that the canister maintains itself; a change to these alone keeps `version`.
+const SYSTEM_FIELDS: &[&str] = &["age", "account_state", "paused_until", "deletion_scheduled_at"];
+
 impl State {
-    
-    pub fn create_account(&mut self, user_id: String, mut params: UserProfileCreationInfo) -> Result<String, String> {
+    // Every profile write goes through here so that the version counter, the derived age and the audit log stay accurate.
+    // A profile identical to the stored one is not written at all.
+    pub fn save_profile(&mut self, user_id: String, mut profile: UserProfileCreationInfo) -> Option<UserProfileCreationInfo> {
+        let now = ic_cdk::api::time();
+        // Profiles whose dob predates server-side parsing keep the age they were given.
+        if let Some(age) = age_from_dob(&profile.params.dob, Date::from_nanos(now)) {
+            profile.params.age = Some(age);
+        }
+        let previous = self.user_profiles.get(&user_id);
+        if previous.as_ref().is_some_and(|previous| encode_profile(previous) == encode_profile(&profile)) {
+            return previous;
+        }
+        let changes = diff_profiles(previous.as_ref(), &profile, self.audit_hash_key().as_deref());
+        if previous.is_none() || changes.iter().any(|change| !SYSTEM_FIELDS.contains(&change.field.as_str())) {
+            profile.version += 1;
+        }
+        if !changes.is_empty() {
+            let entry = AuditEntry {
+                changed_at: now,
+                caller: caller(),
+                version: profile.version,
+                changes,
+            };
+            self.record_profile_change(&user_id, entry);
+        }
+        self.user_profiles.insert(user_id, profile)
+    }
+
+    pub fn create_account(&mut self, user_id: String, params: UserProfileCreationInfo) -> Result<String, DDateError> {
         // Validation
         // if params.params.name.is_none() || params.params.name.as_ref().unwrap().trim().is_empty() {
         //     return Err("Name is required".to_string());
@@ -212,13 +339,15 @@ impl State {
         // if params.params.preferred_gender.is_none() || params.params.preferred_gender.as_ref().unwrap().trim().is_empty() {
         //     return Err("Preferred gender is required".to_string());
         // }
-    
-        // Initialize expired to false
-        params.expired =false ;
-    
+
+        if self.user_profiles.contains_key(&user_id) {
+            return Err(DDateError::Conflict(format!("User profile with id {} already exists", user_id)));
+        }
+        self.index_principal(params.creator_principal, user_id.clone())?;
+
         ic_cdk::println!("Creating profile with user_id: {}", user_id);
-        if self.user_profiles.insert(user_id.clone(), params).is_some() {
-            Err(format!("User profile with id {} already exists", user_id))
+        if self.save_profile(user_id.clone(), params).is_some() {
+            Err(DDateError::Conflict(format!("User profile with id {} already exists", user_id)))
         } else {
             ic_cdk::println!("Profiles after insertion: {:?}", self.user_profiles.iter().map(|(k, _)| k.clone()).collect::<Vec<_>>());
             Ok(format!("User profile created with id: {}", user_id))
@@ -227,72 +356,112 @@ impl State {
 
     
 
-    pub fn update_account(&mut self, user_id: String, new_params: UserProfileParams) -> Result<String, String> {
+    pub fn update_account(&mut self, user_id: String, new_params: UserProfileParams, expected_version: u64) -> Result<String, DDateError> {
         match self.user_profiles.get(&user_id) {
             Some(mut profile) => {
-                if !profile.status {
-                    return Err("Account is inactive".to_string());
+                if !profile.account_state.is_usable() {
+                    return Err(DDateError::Inactive("Account is inactive".to_string()));
                 }
+                profile.expect_version(expected_version)?;
                 profile.params.merge(new_params);
-                self.user_profiles.remove(&user_id);
-                self.user_profiles.insert(user_id.clone(), profile);
+                self.save_profile(user_id.clone(), profile);
                 ic_cdk::println!("Updated profile with user_id: {}", user_id);
                 Ok(format!("User profile updated with id: {}", user_id))
             },
-            None => Err("Profile not found".to_string()),
+            None => Err(DDateError::NotFound("Profile not found".to_string())),
         }
     }
     
 
-    pub fn delete_account(&mut self, user_id: String) -> Result<String, String> {
+    pub fn delete_account(&mut self, user_id: String) -> Result<String, DDateError> {
         match self.user_profiles.get(&user_id) {
             Some(profile) => {
-                if !profile.status {
-                    return Err("Account is inactive".to_string());
+                if profile.account_state.is_locked() {
+                    return Err(DDateError::Inactive(format!("Account is {:?}", profile.account_state)));
                 }
             },
-            None => return Err("Profile not found".to_string()),
+            None => return Err(DDateError::NotFound("Profile not found".to_string())),
         }
-        self.user_profiles.remove(&user_id).ok_or("Profile not found".to_string())?;
-        ic_cdk::println!("Deleted profile with user_id: {}", user_id);
+        self.start_cascading_delete(&user_id, ic_cdk::api::time());
         Ok(format!("User profile deleted with id: {}", user_id))
     }
+
+    // Removes a profile together with its principal index entries, linked devices and recovery secret.
+    pub fn remove_account_records(&mut self, user_id: &str) {
+        self.unindex_user(user_id);
+        self.remove_device_links(user_id);
+        self.recovery_records.remove(&user_id.to_string());
+        self.swipe_quotas.remove(&user_id.to_string());
+        self.user_profiles.remove(&user_id.to_string());
+        ic_cdk::println!("Deleted profile with user_id: {}", user_id);
+    }
     
 
-    pub fn get_account(&self, user_id: String) -> Result<UserProfileCreationInfo, String> {
+    pub fn get_account(&self, viewer_id: Option<&str>, user_id: String) -> Result<ProfileView, DDateError> {
         match self.user_profiles.get(&user_id) {
             Some(profile) => {
-                if !profile.status {
-                    return Err("Account is inactive".to_string());
+                // Owners can always see their own profile, whatever state it is in.
+                if viewer_id != Some(user_id.as_str()) && !profile.account_state.is_usable() {
+                    return Err(DDateError::Inactive("Account is inactive".to_string()));
                 }
                 ic_cdk::println!("Retrieved profile with user_id: {}", user_id);
-                Ok(profile.clone())
+                Ok(self.view_profile(viewer_id, &profile))
             },
-            None => Err("Profile not found".to_string()),
+            None => Err(DDateError::NotFound("Profile not found".to_string())),
         }
     }
     
-    pub fn get_all_accounts(&self, user_id: String, pagination: Pagination) -> Result<PaginatedProfiles, String> {
+    pub fn get_all_accounts(
+        &self,
+        user_id: String,
+        pagination: Pagination,
+        filter: Option<ExtendedProfileFilter>,
+    ) -> Result<PaginatedProfiles, DDateError> {
         let mut suggested_profiles: Vec<UserProfileCreationInfo> = Vec::new();
         let mut matching_profiles: Vec<UserProfileCreationInfo> = Vec::new();
         let mut other_profiles: Vec<UserProfileCreationInfo> = Vec::new();
-    
-        let new_profile = self.user_profiles.get(&user_id).ok_or_else(|| "User not found".to_string())?;
+        let mut incomplete_profiles: Vec<UserProfileCreationInfo> = Vec::new();
+    
+        self.validate_pagination(&pagination)?;
+        let config = self.config();
+        let new_profile = self.user_profiles.get(&user_id).ok_or_else(|| DDateError::NotFound("User not found".to_string()))?;
+        if !new_profile.account_state.is_usable() {
+            return Err(DDateError::Inactive("Account is inactive".to_string()));
+        }
+        let already_swiped = |id: &String| {
+            let params = &new_profile.params;
+            params.rightswipes.as_ref().is_some_and(|swipes| swipes.contains(id))
+                || params.leftswipes.as_ref().is_some_and(|swipes| swipes.contains(id))
+        };
     
         // Iterate over the user profiles in the map
         for entry in self.user_profiles.iter() {
             let (id, profile) = entry;
-            if *id == user_id || profile.expired {
+            if *id == user_id || !profile.account_state.is_discoverable() || already_swiped(&id) {
                 continue;
             }
     
             // Check if the profile has left-swiped the user_id
-            let profile_leftswiped_user = profile.params.leftswipes.as_ref().map_or(false, |leftswipes| leftswipes.contains(&user_id));
+            let profile_leftswiped_user = profile.params.leftswipes.as_ref().is_some_and(|leftswipes| leftswipes.contains(&user_id));
             if profile_leftswiped_user {
                 continue;
             }
+
+            if filter.as_ref().is_some_and(|filter| !filter.matches(profile.params.extended.as_ref())) {
+                continue;
+            }
+
+            let is_incomplete = config
+                .completeness_threshold
+                .is_some_and(|threshold| completeness(&profile.params).score < threshold);
+            if is_incomplete {
+                if config.incomplete_profiles() == IncompleteProfiles::DownRank {
+                    incomplete_profiles.push(profile.clone());
+                }
+                continue;
+            }
     
-            if profile.params.rightswipes.as_ref().map_or(false, |rightswipes| rightswipes.contains(&user_id)) {
+            if profile.params.rightswipes.as_ref().is_some_and(|rightswipes| rightswipes.contains(&user_id)) {
                 suggested_profiles.push(profile.clone());
             } else {
                 let is_preferred = profile.params.age.unwrap_or(0) >= new_profile.params.min_preferred_age.unwrap_or(0) &&
@@ -312,24 +481,30 @@ impl State {
             }
         }
     
-        // Combine the suggested profiles, matching profiles, and other profiles
-        let mut all_profiles = Vec::with_capacity(suggested_profiles.len() + matching_profiles.len() + other_profiles.len());
+        // Combine the suggested profiles, matching profiles, and other profiles, then any incomplete ones
+        let mut all_profiles = Vec::with_capacity(
+            suggested_profiles.len() + matching_profiles.len() + other_profiles.len() + incomplete_profiles.len(),
+        );
         all_profiles.append(&mut suggested_profiles);
         all_profiles.append(&mut matching_profiles);
         all_profiles.append(&mut other_profiles);
+        all_profiles.append(&mut incomplete_profiles);
     
         if all_profiles.is_empty() {
-            return Err("No profiles are available.".to_string());
+            return Err(DDateError::NotFound("No profiles are available.".to_string()));
         }
     
         let total_profiles = all_profiles.len();
         let start = (pagination.page - 1) * pagination.size;
         if start >= total_profiles {
-            return Err("No profiles are matched.".to_string());
+            return Err(DDateError::NotFound("No profiles are matched.".to_string()));
         }
         let end = std::cmp::min(start + pagination.size, total_profiles);
     
-        let paginated_profiles = all_profiles[start..end].to_vec();
+        let paginated_profiles = all_profiles[start..end]
+            .iter()
+            .map(|profile| PublicProfile::new(profile, audience_for(&new_profile, profile)))
+            .collect();
     
         Ok(PaginatedProfiles {
             total_profiles,
@@ -338,7 +513,7 @@ impl State {
     }
     
     
-    pub fn get_all_profiles(&self) -> Result<(usize, Vec<(String, UserProfileCreationInfo)>), String> {
+    pub fn get_all_profiles(&self) -> Result<(usize, Vec<(String, UserProfileCreationInfo)>), DDateError> {
         let all_profiles: Vec<(String, UserProfileCreationInfo)> = self.user_profiles.iter()
             .map(|(user_id, profile)| (user_id.clone(), profile.clone()))
             .collect();
@@ -346,7 +521,7 @@ impl State {
         let count = all_profiles.len();
         
         if count == 0 {
-            return Err("No profiles found".to_string());
+            return Err(DDateError::NotFound("No profiles found".to_string()));
         }
         
         ic_cdk::println!("Retrieved all profiles.");
@@ -355,31 +530,32 @@ impl State {
     
 
 
-    pub fn create_message_internal(&mut self, sender_id: String, receiver_id: String, content: String) -> Result<u64, String> {
+    pub fn create_message_internal(&mut self, sender_id: String, receiver_id: String, content: String) -> Result<u64, DDateError> {
         // Validate input
         if sender_id.trim().is_empty() {
-            return Err("Sender ID is required".to_string());
+            return Err(DDateError::InvalidInput("Sender ID is required".to_string()));
         }
         if receiver_id.trim().is_empty() {
-            return Err("Receiver ID is required".to_string());
+            return Err(DDateError::InvalidInput("Receiver ID is required".to_string()));
         }
         if sender_id == receiver_id {
-            return Err("Sender and receiver IDs must be different".to_string());
+            return Err(DDateError::InvalidInput("Sender and receiver IDs must be different".to_string()));
         }
         if content.trim().is_empty() {
-            return Err("Message content is required".to_string());
+            return Err(DDateError::InvalidInput("Message content is required".to_string()));
         }
+        self.validate_message_length(&content)?;
 
         // Check if sender exists and is active
-        let sender_profile = self.user_profiles.get(&sender_id).ok_or_else(|| format!("Sender ID '{}' does not exist", sender_id))?;
-        if !sender_profile.status {
-            return Err(format!("Sender ID '{}' is inactive", sender_id));
+        let sender_profile = self.user_profiles.get(&sender_id).ok_or_else(|| DDateError::NotFound(format!("Sender ID '{}' does not exist", sender_id)))?;
+        if !sender_profile.account_state.can_message() {
+            return Err(DDateError::Inactive(format!("Sender ID '{}' is inactive", sender_id)));
         }
 
         // Check if receiver exists and is active
-        let receiver_profile = self.user_profiles.get(&receiver_id).ok_or_else(|| format!("Receiver ID '{}' does not exist", receiver_id))?;
-        if !receiver_profile.status {
-            return Err(format!("Receiver ID '{}' is inactive", receiver_id));
+        let receiver_profile = self.user_profiles.get(&receiver_id).ok_or_else(|| DDateError::NotFound(format!("Receiver ID '{}' does not exist", receiver_id)))?;
+        if !receiver_profile.account_state.can_message() {
+            return Err(DDateError::Inactive(format!("Receiver ID '{}' is inactive", receiver_id)));
         }
 
         // Create the message
@@ -400,13 +576,13 @@ impl State {
         Ok(timestamp)
     }
     
-    pub fn read_messages(user_id: &String, other_user_id: &String) -> Result<Vec<Message>, String> {
+    pub fn read_messages(user_id: &String, other_user_id: &String) -> Result<Vec<Message>, DDateError> {
         // Validate input
         if user_id.trim().is_empty() {
-            return Err("User ID is required".to_string());
+            return Err(DDateError::InvalidInput("User ID is required".to_string()));
         }
         if other_user_id.trim().is_empty() {
-            return Err("Other user ID is required".to_string());
+            return Err(DDateError::InvalidInput("Other user ID is required".to_string()));
         }
     
         STATE.with(|state| {
@@ -414,36 +590,37 @@ impl State {
     
             // Check if user exists and is active
             if let Some(user_profile) = state.user_profiles.get(user_id) {
-                if !user_profile.status {
-                    return Err(format!("User ID '{}' is inactive", user_id));
+                if !user_profile.account_state.can_message() {
+                    return Err(DDateError::Inactive(format!("User ID '{}' is inactive", user_id)));
                 }
             } else {
-                return Err(format!("User ID '{}' does not exist", user_id));
+                return Err(DDateError::NotFound(format!("User ID '{}' does not exist", user_id)));
             }
     
             // Check if other user exists and is active
             if let Some(other_user_profile) = state.user_profiles.get(other_user_id) {
-                if !other_user_profile.status {
-                    return Err(format!("Other user ID '{}' is inactive", other_user_id));
+                if !other_user_profile.account_state.can_message() {
+                    return Err(DDateError::Inactive(format!("Other user ID '{}' is inactive", other_user_id)));
                 }
             } else {
-                return Err(format!("Other user ID '{}' does not exist", other_user_id));
+                return Err(DDateError::NotFound(format!("Other user ID '{}' does not exist", other_user_id)));
             }
     
             // Retrieve messages
             let chat_id = Self::get_chat_id(user_id, other_user_id);
             match state.user_messages.get(&chat_id) {
                 Some(messages) => Ok(messages.iter().cloned().collect()),
-                None => Err("No messages found".to_string()),
+                None => Err(DDateError::NotFound("No messages found".to_string())),
             }
         })
     }
     
-    pub fn update_message(&mut self, timestamp: u64, new_content: String) -> Result<String, String> {
+    pub fn update_message(&mut self, timestamp: u64, new_content: String) -> Result<String, DDateError> {
         // Validate input
         if new_content.trim().is_empty() {
-            return Err("New content is required".to_string());
+            return Err(DDateError::InvalidInput("New content is required".to_string()));
         }
+        self.validate_message_length(&new_content)?;
     
         let mut message_found = false;
     
@@ -456,11 +633,11 @@ impl State {
                 for message in messages_data.iter_mut() {
                     if message.timestamp == timestamp {
                         if let Some(sender_profile) = self.user_profiles.get(&message.sender_id) {
-                            if !sender_profile.status {
-                                return Err("Sender's account is inactive".to_string());
+                            if !sender_profile.account_state.can_message() {
+                                return Err(DDateError::Inactive("Sender's account is inactive".to_string()));
                             }
                         } else {
-                            return Err(format!("Sender ID '{}' does not exist", message.sender_id));
+                            return Err(DDateError::NotFound(format!("Sender ID '{}' does not exist", message.sender_id)));
                         }
     
                         message.content = new_content.clone();
@@ -482,12 +659,12 @@ impl State {
         if message_found {
             Ok("Message updated successfully".to_string())
         } else {
-            Err("Message not found".to_string())
+            Err(DDateError::NotFound("Message not found".to_string()))
         }
     }
     
     
-    pub fn delete_message(&mut self, timestamp: u64) -> Result<String, String> {
+    pub fn delete_message(&mut self, timestamp: u64) -> Result<String, DDateError> {
         // Delete message if sender is active
         let mut message_found = false;
     
@@ -500,11 +677,11 @@ impl State {
                 if let Some(pos) = messages_data.iter().position(|m| m.timestamp == timestamp) {
                     let sender_id = messages_data[pos].sender_id.clone();
                     if let Some(sender_profile) = self.user_profiles.get(&sender_id) {
-                        if !sender_profile.status {
-                            return Err("Sender's account is inactive".to_string());
+                        if !sender_profile.account_state.can_message() {
+                            return Err(DDateError::Inactive("Sender's account is inactive".to_string()));
                         }
                     } else {
-                        return Err(format!("Sender ID '{}' does not exist", sender_id));
+                        return Err(DDateError::NotFound(format!("Sender ID '{}' does not exist", sender_id)));
                     }
     
                     messages_data.remove(pos);
@@ -520,7 +697,7 @@ impl State {
         if message_found {
             Ok("Message deleted successfully".to_string())
         } else {
-            Err("Message not found".to_string())
+            Err(DDateError::NotFound("Message not found".to_string()))
         }
     }
     
@@ -528,54 +705,33 @@ impl State {
 
     // Utility function to generate a chat ID
     fn get_chat_id(user1: &str, user2: &str) -> String {
-        let mut users = vec![user1, user2];
+        let mut users = [user1, user2];
         users.sort();
         format!("{}_{}", users[0], users[1])
     }
-
-
-    pub fn set_user_inactive(&mut self, user_id: String) -> Result<String, String> {
-        if let Some(user_profile) = self.user_profiles.get(&user_id) {
-            let mut updated_profile = user_profile.clone();
-            updated_profile.status = false;
-            self.user_profiles.insert(user_id.clone(), updated_profile);
-            ic_cdk::println!("User ID {} has been made inactive.", user_id);
-            Ok(format!("User ID {} has been made inactive.", user_id))
-        } else {
-            Err(format!("User ID '{}' not found", user_id))
-        }
-    }
     
 }
 
 impl From<UserInputParams> for UserProfileParams {
     fn from(input: UserInputParams) -> Self {
         UserProfileParams {
-            gender: input.gender,
+            gender: input.gender.map(Choice::normalized),
             email: input.email,
             name: input.name,
             mobile_number: input.mobile_number,
             dob: input.dob,
-            gender_pronouns: input.gender_pronouns,
+            gender_pronouns: input.gender_pronouns.map(Choice::normalized),
             life_path_number: input.life_path_number,
-            religion: input.religion,
-            // height: input.height,
-            zodiac: input.zodiac,
-            diet: input.diet,
-            // occupation: input.occupation,
-            // looking_for: input.looking_for,
-            smoking: input.smoking,
-            drinking: input.drinking,
+            religion: input.religion.map(Choice::normalized),
+            zodiac: input.zodiac.map(Choice::normalized),
+            diet: input.diet.map(Choice::normalized),
+            smoking: input.smoking.map(Choice::normalized),
+            drinking: input.drinking.map(Choice::normalized),
             hobbies: input.hobbies,
             sports: input.sports,
-            // art_and_culture: input.art_and_culture,
-            // pets: input.pets,
-            // general_habits: input.general_habits,
-            // outdoor_activities: input.outdoor_activities,
-            // travel: input.travel,
-            // movies: input.movies,
-            interests_in: input.interests_in,
-            age: input.age,
+            interests_in: input.interests_in.map(Choice::normalized),
+            // Never taken from the client; `State::save_profile` derives it from `dob`.
+            age: None,
             // location: input.location,
             min_preferred_age: input.min_preferred_age,
             max_preferred_age: input.max_preferred_age,
@@ -589,6 +745,8 @@ impl From<UserInputParams> for UserProfileParams {
             // preferred_location: input.preferred_location,
             introduction: input.introduction,
             images: input.images,
+            privacy: input.privacy,
+            extended: input.extended,
             likes: None,
             matches: None,
             notifications: None,
@@ -626,21 +784,12 @@ impl UserProfileParams {
         if let Some(religion) = other.religion {
             self.religion = Some(religion);
         }
-        // if let Some(height) = other.height {
-        //     self.height = Some(height);
-        // }
         if let Some(zodiac) = other.zodiac {
             self.zodiac = Some(zodiac);
         }
         if let Some(diet) = other.diet {
             self.diet = Some(diet);
         }
-        // if let Some(occupation) = other.occupation {
-        //     self.occupation = Some(occupation);
-        // }
-        // if let Some(looking_for) = other.looking_for {
-        //     self.looking_for = Some(looking_for);
-        // }
         if let Some(smoking) = other.smoking {
             self.smoking = Some(smoking);
         }
@@ -653,24 +802,6 @@ impl UserProfileParams {
         if let Some(sports) = other.sports {
             self.sports = Some(sports);
         }
-        // if let Some(art_and_culture) = other.art_and_culture {
-        //     self.art_and_culture = Some(art_and_culture);
-        // }
-        // if let Some(pets) = other.pets {
-        //     self.pets = Some(pets);
-        // }
-        // if let Some(general_habits) = other.general_habits {
-        //     self.general_habits = Some(general_habits);
-        // }
-        // if let Some(outdoor_activities) = other.outdoor_activities {
-        //     self.outdoor_activities = Some(outdoor_activities);
-        // }
-        // if let Some(travel) = other.travel {
-        //     self.travel = Some(travel);
-        // }
-        // if let Some(movies) = other.movies {
-        //     self.movies = Some(movies);
-        // }
         if let Some(interests_in) = other.interests_in {
             self.interests_in = Some(interests_in);
         }
@@ -716,6 +847,12 @@ impl UserProfileParams {
         if let Some(images) = other.images {
             self.images = Some(images);
         }
+        if let Some(privacy) = other.privacy {
+            self.privacy.get_or_insert_with(PrivacySettings::default).merge(privacy);
+        }
+        if let Some(extended) = other.extended {
+            self.extended.get_or_insert_with(ExtendedProfile::default).merge(extended);
+        }
         if let Some(likes) = other.likes {
             self.likes = Some(likes);
         }
@@ -731,66 +868,95 @@ impl UserProfileParams {
     }
 }
 
-// #[update(guard = "is_anonymous")]
-#[update]
-pub async fn create_an_account(params: UserInputParams) -> Result<String, String> {
+#[update(guard = "is_anonymous")]
+pub async fn create_an_account(params: UserInputParams) -> Result<String, DDateError> {
     let caller = ic_cdk::api::caller();
+    validate_new_profile(&params, read_state(|state| state.age_policy(ic_cdk::api::time())))?;
 
-    let u_ids = raw_rand().await.map_err(|e| format!("Failed to generate random user ID: {:?}", e))?.0;
-    let unique_user_id = format!("{:x}", Sha256::digest(&u_ids));
+    // Fail before spending a raw_rand call when the caller already has an active account.
+    if let Some(existing_id) = read_state(|state| state.find_user_id_by_principal(&caller)) {
+        return Err(DDateError::Conflict(format!("Principal {} already has an active account with id: {}", caller, existing_id)));
+    }
 
-    let profile_info = UserProfileCreationInfo {
-        user_id: unique_user_id.clone(),
-        created_at: ic_cdk::api::time(),
-        creator_principal: caller,
-        params: params.into(), // Convert UserInputParams to UserProfileParams
-        notifications: VecDeque::new(),
-        matched_profiles: Vec::new(),
-        status: true,
-        expired: false, // Initialize expired to false
-    };
+    let unique_user_id = generate_user_id().await?;
+    let profile_info = new_profile_info(unique_user_id.clone(), caller, params.into());
 
     ic_cdk::println!("Creating account with user_id: {}", unique_user_id);
     mutate_state(|state| state.create_account(unique_user_id, profile_info))
 }
 
+pub async fn generate_user_id() -> Result<String, DDateError> {
+    let u_ids = raw_rand()
+        .await
+        .map_err(|e| DDateError::Internal(format!("Failed to generate random user ID: {:?}", e)))?
+        .0;
+    Ok(format!("{:x}", Sha256::digest(&u_ids)))
+}
+
+pub fn new_profile_info(user_id: String, creator_principal: Principal, params: UserProfileParams) -> UserProfileCreationInfo {
+    let created_at = ic_cdk::api::time();
+    UserProfileCreationInfo {
+        user_id,
+        created_at,
+        creator_principal,
+        params,
+        notifications: VecDeque::new(),
+        matched_profiles: Vec::new(),
+        account_state: AccountState::Active,
+        state_changed_at: created_at,
+        paused_until: None,
+        deletion_scheduled_at: None,
+        version: 0,
+    }
+}
+
 
-// #[update(guard = "is_anonymous")]
-#[update]
-pub fn update_an_account(user_id: String, params: UserInputParams) -> Result<String, String> {
+#[update(guard = "is_anonymous")]
+pub fn update_an_account(user_id: String, params: UserInputParams, expected_version: u64) -> Result<String, DDateError> {
     ic_cdk::println!("Updating account with user_id: {}", user_id);
-    let user_profile_params: UserProfileParams = params.into(); // Convert UserInputParams to UserProfileParams
 
-    mutate_state(|state| state.update_account(user_id, user_profile_params))
+    mutate_state(|state| {
+        authorize_user(state, &user_id)?;
+        let profile = state.user_profiles.get(&user_id).ok_or_else(|| DDateError::NotFound("Profile not found".to_string()))?;
+        validate_profile_update(&profile.params, &params, state.age_policy(ic_cdk::api::time()))?;
+
+        let user_profile_params: UserProfileParams = params.into(); // Convert UserInputParams to UserProfileParams
+        state.update_account(user_id, user_profile_params, expected_version)
+    })
 }
 
-// #[update(guard = "is_anonymous")]
-#[update]
-pub fn delete_an_account(user_id: String) -> Result<String, String> {
+#[update(guard = "is_anonymous")]
+pub fn delete_an_account(user_id: String) -> Result<String, DDateError> {
     ic_cdk::println!("Deleting account with user_id: {}", user_id);
-    mutate_state(|state| state.delete_account(user_id))
+    mutate_state(|state| {
+        authorize_user(state, &user_id)?;
+        state.delete_account(user_id)
+    })
+    .inspect(|_| schedule_deletion_tick())
 }
 
 
-// #[query(guard = "is_anonymous")]
-#[query]
-pub fn get_an_account(user_id: String) -> Result<UserProfileCreationInfo, String> {
+#[query(guard = "is_anonymous")]
+pub fn get_an_account(user_id: String) -> Result<ProfileView, DDateError> {
     ic_cdk::println!("Retrieving account with user_id: {}", user_id);
-    read_state(|state| state.get_account(user_id))
-}
-
-// #[query(guard = "is_anonymous")]
-#[query]
-pub fn get_all_accounts(user_id: String, pagination: Pagination) -> Result<PaginatedProfiles, String> {
-    read_state(|state| state.get_all_accounts(user_id, pagination))
+    read_state(|state| {
+        // A deactivated or locked viewer sees other profiles as a stranger would.
+        let viewer_id = caller_user_id(state)
+            .ok()
+            .filter(|viewer_id| *viewer_id == user_id || state.user_profiles.get(viewer_id).is_some_and(|viewer| viewer.account_state.is_usable()));
+        state.get_account(viewer_id.as_deref(), user_id)
+    })
 }
 
-// #[query(guard = "is_anonymous")]
-#[query]
-fn get_all() -> Result<(usize, Vec<(String, UserProfileCreationInfo)>), String> {
-    STATE.with(|state| {
-        let state = state.borrow();
-        state.get_all_profiles()
+#[query(guard = "is_anonymous")]
+pub fn get_all_accounts(
+    user_id: String,
+    pagination: Pagination,
+    filter: Option<ExtendedProfileFilter>,
+) -> Result<PaginatedProfiles, DDateError> {
+    read_state(|state| {
+        authorize_user(state, &user_id)?;
+        state.get_all_accounts(user_id, pagination, filter)
     })
 }
 
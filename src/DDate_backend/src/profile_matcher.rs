@@ -2,14 +2,17 @@
 use candid::CandidType;
 use serde::Deserialize;
 
+use crate::errors::DDateError;
 use crate::profile_creation::{Pagination, UserProfileCreationInfo};
+use crate::privacy::Audience;
+use crate::profile_views::PublicProfile;
 use crate::state_handler::State;
-use crate::mutate_state;
+use crate::{mutate_state, read_state};
 
 #[derive(Clone, Debug, CandidType, Deserialize, Default)]
 pub struct MatchResult {
     pub total_matches: usize,
-    pub paginated_profiles: Vec<UserProfileCreationInfo>,
+    pub paginated_profiles: Vec<PublicProfile>,
     pub error_message: Option<String>,
 }
 
@@ -17,34 +20,29 @@ pub struct MatchResult {
 pub fn find_matches(
     profile_id: &String,
     pagination: Pagination,
-) -> Result<MatchResult, String> {
+) -> Result<MatchResult, DDateError> {
     println!("Finding matches for profile ID: {}", profile_id);
 
     let mut new_profile = mutate_state(|state| {
-        state.user_profiles.get(profile_id).map(|p| p.clone())
-    }).ok_or_else(|| format!("Profile ID '{}' not found", profile_id))?;
+        state.user_profiles.get(profile_id)
+    }).ok_or_else(|| DDateError::NotFound(format!("Profile ID '{}' not found", profile_id)))?;
 
-    if !new_profile.status {
-        return Err("Account is inactive".to_string());
+    if !new_profile.account_state.is_usable() {
+        return Err(DDateError::Inactive("Account is inactive".to_string()));
     }
 
-    if pagination.page == 0 {
-        return Err("Page number must be greater than 0".to_string());
-    }
-
-    if pagination.size == 0 {
-        return Err("Page size must be greater than 0".to_string());
-    }
+    read_state(|state| state.validate_pagination(&pagination))?;
 
     let mut all_matched_profiles: Vec<UserProfileCreationInfo> = Vec::new();
     let mut updated_profiles: Vec<(String, UserProfileCreationInfo)> = Vec::new();
+    let mut new_matches = false;
 
     mutate_state(|state| {
         for (id, existing_profile) in state.user_profiles.iter() {
             let id = id.clone();
-            if &id != profile_id && existing_profile.status {
-                let both_rightswiped = existing_profile.params.rightswipes.as_ref().map_or(false, |rightswipes| rightswipes.contains(profile_id)) &&
-                                       new_profile.params.rightswipes.as_ref().map_or(false, |rightswipes| rightswipes.contains(&id));
+            if &id != profile_id && existing_profile.account_state.is_usable() {
+                let both_rightswiped = existing_profile.params.rightswipes.as_ref().is_some_and(|rightswipes| rightswipes.contains(profile_id)) &&
+                                       new_profile.params.rightswipes.as_ref().is_some_and(|rightswipes| rightswipes.contains(&id));
 
                 if both_rightswiped {
                     println!("Mutual rightswipe match found: {:?}", id);
@@ -52,6 +50,7 @@ pub fn find_matches(
                     // Update matched_profiles for both profiles
                     if !new_profile.matched_profiles.contains(&id) {
                         new_profile.matched_profiles.push(id.clone());
+                        new_matches = true;
                     }
 
                     let mut existing_profile = existing_profile.clone();
@@ -65,13 +64,15 @@ pub fn find_matches(
             }
         }
 
-        // Insert the updated new_profile
-        state.user_profiles.insert(profile_id.clone(), new_profile.clone());
+        // Reads that find no new match leave the profile untouched.
+        if new_matches {
+            state.save_profile(profile_id.clone(), new_profile.clone());
+        }
     });
 
     mutate_state(|state| {
         for (id, updated_profile) in updated_profiles {
-            state.user_profiles.insert(id, updated_profile);
+            state.save_profile(id, updated_profile);
         }
     });
 
@@ -80,10 +81,13 @@ pub fn find_matches(
     let end = std::cmp::min(start + pagination.size, total_matches);
 
     if start >= total_matches {
-        return Err("Page number out of range".to_string());
+        return Err(DDateError::InvalidInput("Page number out of range".to_string()));
     }
 
-    let paginated_profiles = all_matched_profiles[start..end].to_vec();
+    let paginated_profiles = all_matched_profiles[start..end]
+        .iter()
+        .map(|profile| PublicProfile::new(profile, Audience::Match))
+        .collect();
 
     Ok(MatchResult {
         total_matches,
@@ -93,12 +97,12 @@ pub fn find_matches(
 }
 
 
-pub fn remove_matches(state: &mut State, user_id: String) -> Result<String, String> {
-    let user_profile_option = state.user_profiles.get(&user_id).map(|p| p.clone());
+pub fn remove_matches(state: &mut State, user_id: String) -> Result<String, DDateError> {
+    let user_profile_option = state.user_profiles.get(&user_id);
     if let Some(mut user_profile) = user_profile_option {
         // Check if the profile is active
-        if !user_profile.status {
-            return Err("Account is inactive".to_string());
+        if !user_profile.account_state.is_usable() {
+            return Err(DDateError::Inactive("Account is inactive".to_string()));
         }
 
         // Clear the matched profiles for the user
@@ -139,17 +143,17 @@ pub fn remove_matches(state: &mut State, user_id: String) -> Result<String, Stri
         }
 
         // Insert the updated user profile
-        state.user_profiles.insert(user_id.clone(), user_profile);
+        state.save_profile(user_id.clone(), user_profile);
 
         // Apply the collected changes
         for (key, profile) in profiles_to_update {
-            state.user_profiles.insert(key, profile);
+            state.save_profile(key, profile);
         }
 
         ic_cdk::println!("Removed all matches for user ID: {}", user_id);
         Ok(format!("Removed all matches for user ID: {}", user_id))
     } else {
-        Err(format!("User ID '{}' not found", user_id))
+        Err(DDateError::NotFound(format!("User ID '{}' not found", user_id)))
     }
 }
 
@@ -0,0 +1,439 @@
+use candid::CandidType;
+use ic_cdk::update;
+use serde::{Deserialize, Serialize};
+
+use crate::attributes::{Choice, Diet, Drinking, Gender, GenderPronouns, Religion, Smoking, Zodiac};
+use crate::errors::DDateError;
+use crate::guards::{authorize_user, is_anonymous};
+use crate::extended_profile::ExtendedProfile;
+use crate::privacy::PrivacySettings;
+use crate::profile_creation::{UserInputParams, UserProfileParams};
+use crate::state_handler::{mutate_state, State};
+use crate::validation::validate_profile_patch;
+
+#[derive(Debug, Serialize, Clone, Default, Deserialize, CandidType)]
+pub enum TextPatch {
+    #[default]
+    Keep,
+    Set(String),
+    Clear,
+}
+
+#[derive(Debug, Serialize, Clone, Default, Deserialize, CandidType)]
+pub enum NumberPatch {
+    #[default]
+    Keep,
+    Set(u64),
+    Clear,
+}
+
+#[derive(Debug, Serialize, Clone, Default, Deserialize, CandidType)]
+pub enum ChoicePatch<T> {
+    #[default]
+    Keep,
+    Set(T),
+    Clear,
+}
+
+// Add skips entries already in the list; Remove drops every matching entry.
+#[derive(Debug, Serialize, Clone, Default, Deserialize, CandidType)]
+pub enum ListPatch {
+    #[default]
+    Keep,
+    Set(Vec<String>),
+    Clear,
+    Add(Vec<String>),
+    Remove(Vec<String>),
+}
+
+// Partial profile edit where every field says explicitly whether it is kept, replaced or removed.
+#[derive(Debug, Serialize, Clone, Default, Deserialize, CandidType)]
+pub struct ProfilePatch {
+    pub gender: ChoicePatch<Gender>,
+    pub email: TextPatch,
+    pub name: TextPatch,
+    pub mobile_number: TextPatch,
+    pub dob: TextPatch,
+    pub gender_pronouns: ChoicePatch<GenderPronouns>,
+    pub life_path_number: TextPatch,
+    pub religion: ChoicePatch<Religion>,
+    pub zodiac: ChoicePatch<Zodiac>,
+    pub diet: ChoicePatch<Diet>,
+    pub smoking: ChoicePatch<Smoking>,
+    pub drinking: ChoicePatch<Drinking>,
+    pub hobbies: ListPatch,
+    pub sports: ListPatch,
+    pub interests_in: ChoicePatch<Gender>,
+    pub min_preferred_age: NumberPatch,
+    pub max_preferred_age: NumberPatch,
+    pub location_city: TextPatch,
+    pub location_state: TextPatch,
+    pub location_country: TextPatch,
+    pub preferred_city: TextPatch,
+    pub preferred_state: TextPatch,
+    pub preferred_country: TextPatch,
+    pub introduction: TextPatch,
+    pub images: ListPatch,
+    // Merged field by field, like in `update_an_account`.
+    pub privacy: Option<PrivacySettings>,
+    pub extended: ExtendedProfilePatch,
+}
+
+#[derive(Debug, Serialize, Clone, Default, Deserialize, CandidType)]
+pub struct ExtendedProfilePatch {
+    pub height_cm: NumberPatch,
+    pub occupation: TextPatch,
+    pub looking_for: TextPatch,
+    pub pets: TextPatch,
+    pub art_and_culture: ListPatch,
+    pub general_habits: ListPatch,
+    pub outdoor_activities: ListPatch,
+    pub travel: ListPatch,
+    pub movies: ListPatch,
+}
+
+// Each `apply` updates `value` in place, records `field` if it was cleared and returns the value it wrote.
+impl TextPatch {
+    fn apply(&self, field: &'static str, value: &mut Option<String>, cleared: &mut Vec<&'static str>) -> Option<String> {
+        match self {
+            TextPatch::Keep => None,
+            TextPatch::Set(new_value) => {
+                *value = Some(new_value.clone());
+                value.clone()
+            }
+            TextPatch::Clear => {
+                *value = None;
+                cleared.push(field);
+                None
+            }
+        }
+    }
+}
+
+impl NumberPatch {
+    fn apply(&self, field: &'static str, value: &mut Option<u64>, cleared: &mut Vec<&'static str>) -> Option<u64> {
+        match self {
+            NumberPatch::Keep => None,
+            NumberPatch::Set(new_value) => {
+                *value = Some(*new_value);
+                *value
+            }
+            NumberPatch::Clear => {
+                *value = None;
+                cleared.push(field);
+                None
+            }
+        }
+    }
+}
+
+impl<T: Choice + Clone> ChoicePatch<T> {
+    fn apply(&self, field: &'static str, value: &mut Option<T>, cleared: &mut Vec<&'static str>) -> Option<T> {
+        match self {
+            ChoicePatch::Keep => None,
+            ChoicePatch::Set(new_value) => {
+                *value = Some(new_value.clone().normalized());
+                value.clone()
+            }
+            ChoicePatch::Clear => {
+                *value = None;
+                cleared.push(field);
+                None
+            }
+        }
+    }
+}
+
+impl ListPatch {
+    fn apply(
+        &self,
+        field: &'static str,
+        value: &mut Option<Vec<String>>,
+        cleared: &mut Vec<&'static str>,
+    ) -> Option<Vec<String>> {
+        let list = match self {
+            ListPatch::Keep => return None,
+            ListPatch::Clear => {
+                *value = None;
+                cleared.push(field);
+                return None;
+            }
+            ListPatch::Set(items) => items.clone(),
+            ListPatch::Add(items) => {
+                let mut list = value.take().unwrap_or_default();
+                for item in items {
+                    if !list.contains(item) {
+                        list.push(item.clone());
+                    }
+                }
+                list
+            }
+            ListPatch::Remove(items) => {
+                let mut list = value.take().unwrap_or_default();
+                list.retain(|item| !items.contains(item));
+                list
+            }
+        };
+        *value = if list.is_empty() { None } else { Some(list.clone()) };
+        Some(list)
+    }
+}
+
+impl ExtendedProfilePatch {
+    // Returns the whole resulting section when anything in it was written, so that it is validated as one.
+    fn apply(&self, value: &mut Option<ExtendedProfile>, cleared: &mut Vec<&'static str>) -> Option<ExtendedProfile> {
+        let mut extended = value.take().unwrap_or_default();
+        let c = cleared;
+        let written = [
+            self.height_cm.apply("extended.height_cm", &mut extended.height_cm, c).is_some(),
+            self.occupation.apply("extended.occupation", &mut extended.occupation, c).is_some(),
+            self.looking_for.apply("extended.looking_for", &mut extended.looking_for, c).is_some(),
+            self.pets.apply("extended.pets", &mut extended.pets, c).is_some(),
+            self.art_and_culture.apply("extended.art_and_culture", &mut extended.art_and_culture, c).is_some(),
+            self.general_habits.apply("extended.general_habits", &mut extended.general_habits, c).is_some(),
+            self.outdoor_activities.apply("extended.outdoor_activities", &mut extended.outdoor_activities, c).is_some(),
+            self.travel.apply("extended.travel", &mut extended.travel, c).is_some(),
+            self.movies.apply("extended.movies", &mut extended.movies, c).is_some(),
+        ];
+        let is_empty = extended == ExtendedProfile::default();
+        let result = written.contains(&true).then(|| extended.clone());
+        *value = if is_empty { None } else { Some(extended) };
+        result
+    }
+}
+
+impl ProfilePatch {
+    // Applies the patch to `params` and returns the written values and cleared fields for validation.
+    pub fn apply(&self, params: &mut UserProfileParams) -> (UserInputParams, Vec<&'static str>) {
+        let mut cleared = Vec::new();
+        let c = &mut cleared;
+        let written = UserInputParams {
+            gender: self.gender.apply("gender", &mut params.gender, c),
+            email: self.email.apply("email", &mut params.email, c),
+            name: self.name.apply("name", &mut params.name, c),
+            mobile_number: self.mobile_number.apply("mobile_number", &mut params.mobile_number, c),
+            dob: self.dob.apply("dob", &mut params.dob, c),
+            gender_pronouns: self.gender_pronouns.apply("gender_pronouns", &mut params.gender_pronouns, c),
+            life_path_number: self.life_path_number.apply("life_path_number", &mut params.life_path_number, c),
+            religion: self.religion.apply("religion", &mut params.religion, c),
+            zodiac: self.zodiac.apply("zodiac", &mut params.zodiac, c),
+            diet: self.diet.apply("diet", &mut params.diet, c),
+            smoking: self.smoking.apply("smoking", &mut params.smoking, c),
+            drinking: self.drinking.apply("drinking", &mut params.drinking, c),
+            hobbies: self.hobbies.apply("hobbies", &mut params.hobbies, c),
+            sports: self.sports.apply("sports", &mut params.sports, c),
+            interests_in: self.interests_in.apply("interests_in", &mut params.interests_in, c),
+            min_preferred_age: self.min_preferred_age.apply("min_preferred_age", &mut params.min_preferred_age, c),
+            max_preferred_age: self.max_preferred_age.apply("max_preferred_age", &mut params.max_preferred_age, c),
+            location_city: self.location_city.apply("location_city", &mut params.location_city, c),
+            location_state: self.location_state.apply("location_state", &mut params.location_state, c),
+            location_country: self.location_country.apply("location_country", &mut params.location_country, c),
+            preferred_city: self.preferred_city.apply("preferred_city", &mut params.preferred_city, c),
+            preferred_state: self.preferred_state.apply("preferred_state", &mut params.preferred_state, c),
+            preferred_country: self.preferred_country.apply("preferred_country", &mut params.preferred_country, c),
+            introduction: self.introduction.apply("introduction", &mut params.introduction, c),
+            images: self.images.apply("images", &mut params.images, c),
+            // Derived from `dob` when the profile is saved.
+            age: None,
+            privacy: None,
+            extended: self.extended.apply(&mut params.extended, c),
+        };
+        if let Some(privacy) = &self.privacy {
+            params.privacy.get_or_insert_with(PrivacySettings::default).merge(privacy.clone());
+        }
+        (written, cleared)
+    }
+}
+
+impl State {
+    pub fn patch_account(&mut self, user_id: String, patch: ProfilePatch, expected_version: u64) -> Result<String, DDateError> {
+        let mut profile = self
+            .user_profiles
+            .get(&user_id)
+            .ok_or_else(|| DDateError::NotFound("Profile not found".to_string()))?;
+        if !profile.account_state.is_usable() {
+            return Err(DDateError::Inactive("Account is inactive".to_string()));
+        }
+        profile.expect_version(expected_version)?;
+
+        let current = profile.params.clone();
+        let (written, cleared) = patch.apply(&mut profile.params);
+        validate_profile_patch(&current, &written, &cleared, self.age_policy(ic_cdk::api::time()))?;
+
+        self.save_profile(user_id.clone(), profile);
+        ic_cdk::println!("Patched profile with user_id: {}", user_id);
+        Ok(format!("User profile patched with id: {}", user_id))
+    }
+}
+
+#[update(guard = "is_anonymous")]
+pub fn patch_an_account(user_id: String, patch: ProfilePatch, expected_version: u64) -> Result<String, DDateError> {
+    ic_cdk::println!("Patching account with user_id: {}", user_id);
+    mutate_state(|state| {
+        authorize_user(state, &user_id)?;
+        state.patch_account(user_id, patch, expected_version)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::privacy::Visibility;
+    use candid::Encode;
+
+    fn strings(items: &[&str]) -> Option<Vec<String>> {
+        Some(items.iter().map(|item| item.to_string()).collect())
+    }
+
+    fn encode(params: &UserProfileParams) -> Vec<u8> {
+        Encode!(params).unwrap()
+    }
+
+    fn params() -> UserProfileParams {
+        UserProfileParams {
+            name: Some("Ann".to_string()),
+            email: Some("ann@example.com".to_string()),
+            gender: Some(Gender::Female),
+            min_preferred_age: Some(25),
+            hobbies: strings(&["Reading", "Chess"]),
+            ..UserProfileParams::default()
+        }
+    }
+
+    #[test]
+    fn keep_leaves_the_profile_untouched() {
+        let mut params = params();
+        let (written, cleared) = ProfilePatch::default().apply(&mut params);
+
+        assert_eq!(encode(&params), encode(&self::params()));
+        assert!(cleared.is_empty());
+        assert!(written.name.is_none() && written.hobbies.is_none() && written.extended.is_none());
+    }
+
+    #[test]
+    fn set_and_clear_are_reported_separately() {
+        let mut params = params();
+        let patch = ProfilePatch {
+            name: TextPatch::Set("Anna".to_string()),
+            email: TextPatch::Clear,
+            min_preferred_age: NumberPatch::Set(30),
+            gender: ChoicePatch::Clear,
+            ..ProfilePatch::default()
+        };
+        let (written, cleared) = patch.apply(&mut params);
+
+        assert_eq!(params.name.as_deref(), Some("Anna"));
+        assert_eq!(params.email, None);
+        assert_eq!(params.min_preferred_age, Some(30));
+        assert_eq!(params.gender, None);
+        assert_eq!(written.name.as_deref(), Some("Anna"));
+        assert_eq!(written.min_preferred_age, Some(30));
+        assert_eq!(written.email, None);
+        assert_eq!(cleared, vec!["gender", "email"]);
+    }
+
+    #[test]
+    fn set_choice_is_normalized() {
+        let mut params = params();
+        let patch = ProfilePatch {
+            interests_in: ChoicePatch::Set(Gender::Other("male".to_string())),
+            ..ProfilePatch::default()
+        };
+        let (written, _) = patch.apply(&mut params);
+
+        assert_eq!(params.interests_in, Some(Gender::Male));
+        assert_eq!(written.interests_in, Some(Gender::Male));
+    }
+
+    #[test]
+    fn list_add_and_remove_edit_the_existing_list() {
+        let mut params = params();
+        let patch = ProfilePatch {
+            hobbies: ListPatch::Add(vec!["Chess".to_string(), "Hiking".to_string()]),
+            sports: ListPatch::Add(vec!["Tennis".to_string()]),
+            ..ProfilePatch::default()
+        };
+        patch.apply(&mut params);
+        assert_eq!(params.hobbies, strings(&["Reading", "Chess", "Hiking"]));
+        assert_eq!(params.sports, strings(&["Tennis"]));
+
+        let patch = ProfilePatch {
+            hobbies: ListPatch::Remove(vec!["Reading".to_string()]),
+            sports: ListPatch::Remove(vec!["Tennis".to_string()]),
+            ..ProfilePatch::default()
+        };
+        let (written, cleared) = patch.apply(&mut params);
+        assert_eq!(params.hobbies, strings(&["Chess", "Hiking"]));
+        // Removing the last entry stores no list but still reports the empty result, not a clear.
+        assert_eq!(params.sports, None);
+        assert_eq!(written.sports, Some(Vec::new()));
+        assert!(cleared.is_empty());
+    }
+
+    #[test]
+    fn extended_section_is_written_as_a_whole() {
+        let mut params = params();
+        params.extended = Some(ExtendedProfile {
+            occupation: Some("Teacher".to_string()),
+            ..ExtendedProfile::default()
+        });
+        let patch = ProfilePatch {
+            extended: ExtendedProfilePatch {
+                height_cm: NumberPatch::Set(170),
+                ..ExtendedProfilePatch::default()
+            },
+            ..ProfilePatch::default()
+        };
+        let (written, _) = patch.apply(&mut params);
+
+        let expected = ExtendedProfile {
+            height_cm: Some(170),
+            occupation: Some("Teacher".to_string()),
+            ..ExtendedProfile::default()
+        };
+        assert_eq!(params.extended, Some(expected.clone()));
+        assert_eq!(written.extended, Some(expected));
+    }
+
+    #[test]
+    fn clearing_the_last_extended_field_drops_the_section() {
+        let mut params = params();
+        params.extended = Some(ExtendedProfile {
+            pets: Some("Cat".to_string()),
+            ..ExtendedProfile::default()
+        });
+        let patch = ProfilePatch {
+            extended: ExtendedProfilePatch {
+                pets: TextPatch::Clear,
+                ..ExtendedProfilePatch::default()
+            },
+            ..ProfilePatch::default()
+        };
+        let (written, cleared) = patch.apply(&mut params);
+
+        assert_eq!(params.extended, None);
+        assert_eq!(written.extended, None);
+        assert_eq!(cleared, vec!["extended.pets"]);
+    }
+
+    #[test]
+    fn privacy_is_merged_field_by_field() {
+        let mut params = params();
+        params.privacy = Some(PrivacySettings {
+            email: Some(Visibility::Everyone),
+            ..PrivacySettings::default()
+        });
+        let patch = ProfilePatch {
+            privacy: Some(PrivacySettings {
+                dob: Some(Visibility::Nobody),
+                ..PrivacySettings::default()
+            }),
+            ..ProfilePatch::default()
+        };
+        patch.apply(&mut params);
+
+        let privacy = params.privacy.unwrap();
+        assert_eq!(privacy.email, Some(Visibility::Everyone));
+        assert_eq!(privacy.dob, Some(Visibility::Nobody));
+    }
+}
@@ -0,0 +1,144 @@
+use candid::{CandidType, Principal};
+use serde::{Deserialize, Serialize};
+
+use crate::attributes::{Diet, Drinking, Gender, GenderPronouns, Religion, Smoking, Zodiac};
+use crate::extended_profile::ExtendedProfile;
+use crate::lifecycle::AccountState;
+use crate::privacy::{visible_to, Audience};
+use crate::profile_creation::{UserProfileCreationInfo, UserProfileParams};
+use crate::state_handler::State;
+
+// Someone else's profile, with every privacy-controlled field filtered for the viewer.
+#[derive(Debug, Serialize, Clone, Deserialize, CandidType)]
+pub struct PublicProfile {
+    pub user_id: String,
+    pub name: Option<String>,
+    pub age: Option<u64>,
+    pub email: Option<String>,
+    pub mobile_number: Option<String>,
+    pub dob: Option<String>,
+    pub gender: Option<Gender>,
+    pub gender_pronouns: Option<GenderPronouns>,
+    pub life_path_number: Option<String>,
+    pub religion: Option<Religion>,
+    pub zodiac: Option<Zodiac>,
+    pub diet: Option<Diet>,
+    pub smoking: Option<Smoking>,
+    pub drinking: Option<Drinking>,
+    pub hobbies: Option<Vec<String>>,
+    pub sports: Option<Vec<String>>,
+    pub interests_in: Option<Gender>,
+    pub location_city: Option<String>,
+    pub location_state: Option<String>,
+    pub location_country: Option<String>,
+    pub introduction: Option<String>,
+    pub images: Option<Vec<String>>,
+    pub extended: Option<ExtendedProfile>,
+}
+
+// Everything about the caller's own account except its notification queue, which has its own endpoint.
+#[derive(Debug, Serialize, Clone, Deserialize, CandidType)]
+pub struct OwnerProfile {
+    pub user_id: String,
+    pub created_at: u64,
+    pub creator_principal: Principal,
+    pub params: UserProfileParams,
+    pub matched_profiles: Vec<String>,
+    pub account_state: AccountState,
+    pub state_changed_at: u64,
+    pub paused_until: Option<u64>,
+    pub deletion_scheduled_at: Option<u64>,
+    pub version: u64,
+}
+
+#[derive(Debug, Serialize, Clone, Deserialize, CandidType)]
+pub enum ProfileView {
+    Owner(Box<OwnerProfile>),
+    // Same shape as Public, but fields shared with matches only are filled in.
+    Match(PublicProfile),
+    Public(PublicProfile),
+}
+
+impl PublicProfile {
+    pub fn new(profile: &UserProfileCreationInfo, audience: Audience) -> Self {
+        let params = &profile.params;
+        let privacy = params.privacy.clone().unwrap_or_default();
+        PublicProfile {
+            user_id: profile.user_id.clone(),
+            name: params.name.clone(),
+            age: params.age,
+            email: visible_to(&params.email, privacy.email(), audience),
+            mobile_number: visible_to(&params.mobile_number, privacy.mobile_number(), audience),
+            dob: visible_to(&params.dob, privacy.dob(), audience),
+            gender: params.gender.clone(),
+            gender_pronouns: params.gender_pronouns.clone(),
+            life_path_number: params.life_path_number.clone(),
+            religion: visible_to(&params.religion, privacy.religion(), audience),
+            zodiac: params.zodiac.clone(),
+            diet: params.diet.clone(),
+            smoking: params.smoking.clone(),
+            drinking: params.drinking.clone(),
+            hobbies: params.hobbies.clone(),
+            sports: params.sports.clone(),
+            interests_in: params.interests_in.clone(),
+            location_city: visible_to(&params.location_city, privacy.location_city(), audience),
+            location_state: params.location_state.clone(),
+            location_country: params.location_country.clone(),
+            introduction: params.introduction.clone(),
+            images: visible_to(&params.images, privacy.images(), audience),
+            extended: params.extended.clone(),
+        }
+    }
+}
+
+impl From<&UserProfileCreationInfo> for OwnerProfile {
+    fn from(profile: &UserProfileCreationInfo) -> Self {
+        let mut params = profile.params.clone();
+        params.notifications = None;
+        OwnerProfile {
+            user_id: profile.user_id.clone(),
+            created_at: profile.created_at,
+            creator_principal: profile.creator_principal,
+            params,
+            matched_profiles: profile.matched_profiles.clone(),
+            account_state: profile.account_state,
+            state_changed_at: profile.state_changed_at,
+            paused_until: profile.paused_until,
+            deletion_scheduled_at: profile.deletion_scheduled_at,
+            version: profile.version,
+        }
+    }
+}
+
+// Two users are matched once each has right-swiped the other.
+pub fn is_mutual_match(a: &UserProfileCreationInfo, b: &UserProfileCreationInfo) -> bool {
+    let rightswiped = |from: &UserProfileCreationInfo, to: &UserProfileCreationInfo| {
+        from.params.rightswipes.as_ref().is_some_and(|rightswipes| rightswipes.contains(&to.user_id))
+    };
+    rightswiped(a, b) && rightswiped(b, a)
+}
+
+pub fn audience_for(viewer: &UserProfileCreationInfo, profile: &UserProfileCreationInfo) -> Audience {
+    if is_mutual_match(viewer, profile) {
+        Audience::Match
+    } else {
+        Audience::Everyone
+    }
+}
+
+impl State {
+    // Picks the view of `profile` that `viewer_id` is entitled to; callers without an account get the public one.
+    pub fn view_profile(&self, viewer_id: Option<&str>, profile: &UserProfileCreationInfo) -> ProfileView {
+        let Some(viewer_id) = viewer_id else {
+            return ProfileView::Public(PublicProfile::new(profile, Audience::Everyone));
+        };
+        if viewer_id == profile.user_id {
+            return ProfileView::Owner(Box::new(profile.into()));
+        }
+        let viewer = self.user_profiles.get(&viewer_id.to_string());
+        match viewer.map(|viewer| audience_for(&viewer, profile)) {
+            Some(Audience::Match) => ProfileView::Match(PublicProfile::new(profile, Audience::Match)),
+            _ => ProfileView::Public(PublicProfile::new(profile, Audience::Everyone)),
+        }
+    }
+}
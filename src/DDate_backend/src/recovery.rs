@@ -0,0 +1,197 @@
+use candid::{CandidType, Principal};
+use ic_cdk::api::management_canister::main::raw_rand;
+use ic_cdk::{caller, query, update};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::errors::DDateError;
+use crate::guards::{caller_usable_user_id, caller_user_id, is_anonymous};
+use crate::profile_creation::{Notification, NotificationType};
+use crate::state_handler::{mutate_state, read_state, Candid, State};
+
+const NANOS_PER_HOUR: u64 = 60 * 60 * 1_000_000_000;
+// An account can only be recovered once per day.
+const RECOVERY_COOLDOWN_NANOS: u64 = 24 * NANOS_PER_HOUR;
+// Too many wrong secrets lock recovery for an hour.
+const MAX_FAILED_ATTEMPTS: u32 = 5;
+const FAILED_ATTEMPT_LOCKOUT_NANOS: u64 = NANOS_PER_HOUR;
+const MIN_SECRET_LENGTH: usize = 12;
+
+#[derive(Debug, Serialize, Clone, Deserialize, CandidType)]
+pub struct RecoveryRecord {
+    pub secret_hash: Vec<u8>,
+    pub salt: Vec<u8>,
+    pub registered_at: u64,
+    pub last_recovered_at: Option<u64>,
+    pub failed_attempts: u32,
+    pub locked_until: Option<u64>,
+}
+
+#[derive(Debug, Serialize, Clone, Deserialize, CandidType)]
+pub struct RecoveryStatus {
+    pub registered_at: u64,
+    pub last_recovered_at: Option<u64>,
+    pub next_recovery_allowed_at: Option<u64>,
+}
+
+fn hash_secret(salt: &[u8], secret: &str) -> Vec<u8> {
+    let mut hasher = Sha256::new();
+    hasher.update(salt);
+    hasher.update(secret.as_bytes());
+    hasher.finalize().to_vec()
+}
+
+impl RecoveryRecord {
+    fn next_recovery_allowed_at(&self) -> Option<u64> {
+        let cooldown_end = self.last_recovered_at.map(|at| at + RECOVERY_COOLDOWN_NANOS);
+        match (cooldown_end, self.locked_until) {
+            (Some(a), Some(b)) => Some(a.max(b)),
+            (a, b) => a.or(b),
+        }
+    }
+}
+
+impl State {
+    pub fn register_recovery_secret(&mut self, user_id: String, secret: &str, salt: Vec<u8>, now: u64) -> Result<String, DDateError> {
+        if secret.chars().count() < MIN_SECRET_LENGTH {
+            return Err(DDateError::InvalidInput(format!("Recovery secret must be at least {} characters", MIN_SECRET_LENGTH)));
+        }
+
+        let last_recovered_at = self.recovery_records.get(&user_id).and_then(|record| record.last_recovered_at);
+        let record = RecoveryRecord {
+            secret_hash: hash_secret(&salt, secret),
+            salt,
+            registered_at: now,
+            last_recovered_at,
+            failed_attempts: 0,
+            locked_until: None,
+        };
+        self.recovery_records.insert(user_id.clone(), Candid(record));
+
+        ic_cdk::println!("Registered recovery secret for user_id: {}", user_id);
+        Ok(format!("Recovery secret registered for user ID: {}", user_id))
+    }
+
+    // Moves `user_id` over to `new_principal` if `secret` matches the registered one.
+    pub fn recover_account(&mut self, user_id: String, secret: &str, new_principal: Principal, now: u64) -> Result<String, DDateError> {
+        let mut record = self
+            .recovery_records
+            .get(&user_id)
+            .ok_or_else(|| DDateError::NotFound(format!("No recovery secret registered for user ID '{}'", user_id)))?
+            .0;
+
+        if let Some(allowed_at) = record.next_recovery_allowed_at() {
+            if now < allowed_at {
+                return Err(DDateError::RateLimited(format!(
+                    "Recovery for user ID '{}' is on cooldown until {}",
+                    user_id, allowed_at
+                )));
+            }
+        }
+
+        if hash_secret(&record.salt, secret) != record.secret_hash {
+            record.failed_attempts += 1;
+            if record.failed_attempts >= MAX_FAILED_ATTEMPTS {
+                record.failed_attempts = 0;
+                record.locked_until = Some(now + FAILED_ATTEMPT_LOCKOUT_NANOS);
+            }
+            self.recovery_records.insert(user_id, Candid(record));
+            return Err(DDateError::Unauthorized("Recovery secret does not match".to_string()));
+        }
+
+        let mut profile = self
+            .user_profiles
+            .get(&user_id)
+            .ok_or_else(|| DDateError::NotFound(format!("User ID '{}' not found", user_id)))?;
+        if !profile.account_state.is_usable() {
+            return Err(DDateError::Inactive("Account is inactive".to_string()));
+        }
+        if let Some(existing_id) = self.find_user_id_by_principal(&new_principal) {
+            return Err(DDateError::Conflict(format!(
+                "Principal {} already has an active account with id: {}",
+                new_principal, existing_id
+            )));
+        }
+
+        // The lost wallet and every device linked through it lose access. The old principal is told
+        // through its own notice queue, since it can no longer read the profile's notifications.
+        let old_principal = profile.creator_principal;
+        let recovered = Notification {
+            sender_id: user_id.clone(),
+            receiver_id: user_id.clone(),
+            notification_type: NotificationType::AccountRecovered,
+            old_principal: Some(old_principal),
+        };
+        self.push_principal_notice(old_principal, recovered.clone());
+        self.unindex_user(&user_id);
+        self.remove_device_links(&user_id);
+
+        profile.creator_principal = new_principal;
+        self.push_notification(&mut profile, recovered);
+        self.save_profile(user_id.clone(), profile);
+        self.index_principal(new_principal, user_id.clone())?;
+
+        record.failed_attempts = 0;
+        record.locked_until = None;
+        record.last_recovered_at = Some(now);
+        self.recovery_records.insert(user_id.clone(), Candid(record));
+
+        ic_cdk::println!("Recovered user_id {} from principal {} to {}", user_id, old_principal, new_principal);
+        Ok(user_id)
+    }
+}
+
+#[update(guard = "is_anonymous")]
+pub async fn set_recovery_secret(secret: String) -> Result<String, DDateError> {
+    read_state(caller_usable_user_id)?;
+    let salt = raw_rand()
+        .await
+        .map_err(|e| DDateError::Internal(format!("Failed to generate salt: {:?}", e)))?
+        .0;
+
+    mutate_state(|state| {
+        // The account may have been deactivated or locked while raw_rand was in flight.
+        let user_id = caller_usable_user_id(state)?;
+        state.register_recovery_secret(user_id, &secret, salt, ic_cdk::api::time())
+    })
+}
+
+#[update(guard = "is_anonymous")]
+pub fn recover_account(user_id: String, secret: String) -> Result<String, DDateError> {
+    let new_principal = caller();
+    mutate_state(|state| state.recover_account(user_id, &secret, new_principal, ic_cdk::api::time()))
+}
+
+// Notices for the caller's principal, readable whether or not it still owns an account.
+#[query(guard = "is_anonymous")]
+pub fn get_principal_notices() -> Vec<Notification> {
+    read_state(|state| {
+        state
+            .principal_notices
+            .get(&caller())
+            .map(|notices| notices.0.into_iter().collect())
+            .unwrap_or_default()
+    })
+}
+
+#[update(guard = "is_anonymous")]
+pub fn clear_principal_notices() -> String {
+    mutate_state(|state| state.principal_notices.remove(&caller()));
+    "Principal notices cleared.".to_string()
+}
+
+#[query(guard = "is_anonymous")]
+pub fn get_recovery_status() -> Result<RecoveryStatus, DDateError> {
+    read_state(|state| {
+        let user_id = caller_user_id(state)?;
+        let record = state
+            .recovery_records
+            .get(&user_id)
+            .ok_or_else(|| DDateError::NotFound(format!("No recovery secret registered for user ID '{}'", user_id)))?;
+        Ok(RecoveryStatus {
+            registered_at: record.registered_at,
+            last_recovered_at: record.last_recovered_at,
+            next_recovery_allowed_at: record.next_recovery_allowed_at(),
+        })
+    })
+}
@@ -4,7 +4,11 @@ use std::collections::HashSet;
 use candid::CandidType;
 use serde::{Deserialize, Serialize};
 
-use crate::{profile_creation::{Pagination,UserProfileCreationInfo},state_handler::State,MatchResult};
+use crate::errors::DDateError;
+use crate::profile_views::{audience_for, PublicProfile};
+use crate::{profile_creation::{Pagination,UserProfileCreationInfo},state_handler::{Candid, State}};
+
+const NANOS_PER_DAY: u64 = 24 * 60 * 60 * 1_000_000_000;
 
 #[derive(Clone, Deserialize, CandidType, Debug, Serialize)]
 pub struct PaginatedResult {
@@ -13,26 +17,65 @@ pub struct PaginatedResult {
     pub error_message: Option<String>,
 }
 
+// Profiles a user has swiped on, as seen by that user.
+#[derive(Clone, Deserialize, CandidType, Debug, Serialize)]
+pub struct SwipedProfiles {
+    pub total_profiles: usize,
+    pub paginated_profiles: Vec<PublicProfile>,
+}
+
 #[derive(CandidType, Deserialize)]
 pub struct SwipeInput {
     pub sender_id: String,
     pub receiver_id: String,
 }
 
+// Swipes made by a user on `day` (days since the Unix epoch).
+#[derive(Clone, Deserialize, CandidType, Debug, Serialize)]
+pub struct SwipeQuota {
+    pub day: u64,
+    pub count: u64,
+}
+
+impl State {
+    // Counts a swipe against the user's daily quota, failing once the configured limit is reached.
+    pub fn consume_swipe_quota(&mut self, user_id: &str, now: u64) -> Result<(), DDateError> {
+        let limit = self.config().daily_swipe_limit;
+        if limit == 0 {
+            return Ok(());
+        }
+
+        let day = now / NANOS_PER_DAY;
+        let mut quota = self
+            .swipe_quotas
+            .get(&user_id.to_string())
+            .map(|quota| quota.0)
+            .filter(|quota| quota.day == day)
+            .unwrap_or(SwipeQuota { day, count: 0 });
+
+        if quota.count >= limit {
+            return Err(DDateError::RateLimited(format!("Daily swipe limit of {} reached", limit)));
+        }
+        quota.count += 1;
+        self.swipe_quotas.insert(user_id.to_string(), Candid(quota));
+        Ok(())
+    }
+}
+
 pub fn like_profile(
     state: &mut State,
     current_user_id: String,
     liked_user_id: String,
-) -> Result<String, String> {
-    let current_user_profile = state.user_profiles.get(&current_user_id).ok_or_else(|| format!("Current user ID {} does not exist.", current_user_id))?;
-    let liked_user_profile = state.user_profiles.get(&liked_user_id).ok_or_else(|| format!("Liked user ID {} does not exist.", liked_user_id))?;
+) -> Result<String, DDateError> {
+    let current_user_profile = state.user_profiles.get(&current_user_id).ok_or_else(|| DDateError::NotFound(format!("Current user ID {} does not exist.", current_user_id)))?;
+    let liked_user_profile = state.user_profiles.get(&liked_user_id).ok_or_else(|| DDateError::NotFound(format!("Liked user ID {} does not exist.", liked_user_id)))?;
 
-    if !current_user_profile.status {
-        return Err("Current user's account is inactive".to_string());
+    if !current_user_profile.account_state.is_usable() {
+        return Err(DDateError::Inactive("Current user's account is inactive".to_string()));
     }
 
-    if !liked_user_profile.status {
-        return Err("Liked user's account is inactive".to_string());
+    if !liked_user_profile.account_state.is_discoverable() {
+        return Err(DDateError::Inactive("Liked user's account is inactive".to_string()));
     }
 
     let mut current_user_profile = current_user_profile.clone();
@@ -45,7 +88,7 @@ pub fn like_profile(
         current_user_profile.params.likes = Some(new_likes);
     }
 
-    state.user_profiles.insert(current_user_id.clone(), current_user_profile);
+    state.save_profile(current_user_id.clone(), current_user_profile);
 
     ic_cdk::println!("liked!");
 
@@ -60,7 +103,7 @@ pub fn like_profile(
 pub fn leftswipe_profile(
     state: &mut State,
     input: SwipeInput,
-) -> Result<String, String> {
+) -> Result<String, DDateError> {
     let SwipeInput {
         sender_id,
         receiver_id,
@@ -68,22 +111,24 @@ pub fn leftswipe_profile(
     } = input;
 
     if sender_id == receiver_id {
-        return Err("User cannot leftswipe themselves.".to_string());
+        return Err(DDateError::InvalidInput("User cannot leftswipe themselves.".to_string()));
     }
 
     let swiping_user_profile = state.user_profiles.get(&sender_id)
-        .ok_or_else(|| format!("Swiping user ID {} does not exist.", sender_id))?;
+        .ok_or_else(|| DDateError::NotFound(format!("Swiping user ID {} does not exist.", sender_id)))?;
     let swiped_user_profile = state.user_profiles.get(&receiver_id)
-        .ok_or_else(|| format!("Swiped user ID {} does not exist.", receiver_id))?;
+        .ok_or_else(|| DDateError::NotFound(format!("Swiped user ID {} does not exist.", receiver_id)))?;
 
-    if !swiping_user_profile.status {
-        return Err("Swiping user account is inactive".to_string());
+    if !swiping_user_profile.account_state.is_usable() {
+        return Err(DDateError::Inactive("Swiping user account is inactive".to_string()));
     }
 
-    if !swiped_user_profile.status {
-        return Err("Swiped user account is inactive".to_string());
+    if !swiped_user_profile.account_state.is_discoverable() {
+        return Err(DDateError::Inactive("Swiped user account is inactive".to_string()));
     }
 
+    state.consume_swipe_quota(&sender_id, ic_cdk::api::time())?;
+
     let mut swiping_user_profile = swiping_user_profile.clone();
 
     // Add to leftswipes
@@ -96,15 +141,7 @@ pub fn leftswipe_profile(
     }
 
     // Update profiles in state
-    state.user_profiles.insert(sender_id.clone(), swiping_user_profile.clone());
-
-    // Update expired status of swiped user
-    if let Some(mut swiped_user_profile) = state.user_profiles.remove(&receiver_id) {
-        swiped_user_profile.expired = true;
-        state.user_profiles.insert(receiver_id.clone(), swiped_user_profile);
-    } else {
-        return Err(format!("Failed to update expired status for user ID {}", receiver_id));
-    }
+    state.save_profile(sender_id.clone(), swiping_user_profile.clone());
 
     ic_cdk::println!("Leftswiped! Swiping user profile: {:?}", swiping_user_profile);
     Ok("Left swiped successfully.".to_string())
@@ -114,7 +151,7 @@ pub fn leftswipe_profile(
 pub fn rightswipe_profile(
     state: &mut State,
     input: SwipeInput,
-) -> Result<String, String> {
+) -> Result<String, DDateError> {
     let SwipeInput {
         sender_id,
         receiver_id,
@@ -122,22 +159,24 @@ pub fn rightswipe_profile(
     } = input;
 
     if sender_id == receiver_id {
-        return Err("User cannot rightswipe themselves.".to_string());
+        return Err(DDateError::InvalidInput("User cannot rightswipe themselves.".to_string()));
     }
 
     let swiping_user_profile = state.user_profiles.get(&sender_id)
-        .ok_or_else(|| format!("Swiping user ID {} does not exist.", sender_id))?;
+        .ok_or_else(|| DDateError::NotFound(format!("Swiping user ID {} does not exist.", sender_id)))?;
     let swiped_user_profile = state.user_profiles.get(&receiver_id)
-        .ok_or_else(|| format!("Swiped user ID {} does not exist.", receiver_id))?;
+        .ok_or_else(|| DDateError::NotFound(format!("Swiped user ID {} does not exist.", receiver_id)))?;
 
-    if !swiping_user_profile.status {
-        return Err("Swiping user account is inactive".to_string());
+    if !swiping_user_profile.account_state.is_usable() {
+        return Err(DDateError::Inactive("Swiping user account is inactive".to_string()));
     }
 
-    if !swiped_user_profile.status {
-        return Err("Swiped user account is inactive".to_string());
+    if !swiped_user_profile.account_state.is_discoverable() {
+        return Err(DDateError::Inactive("Swiped user account is inactive".to_string()));
     }
 
+    state.consume_swipe_quota(&sender_id, ic_cdk::api::time())?;
+
     let mut swiping_user_profile = swiping_user_profile.clone();
 
     // Add to rightswipes
@@ -150,35 +189,22 @@ pub fn rightswipe_profile(
     }
 
     // Update profiles in state
-    state.user_profiles.insert(sender_id.clone(), swiping_user_profile.clone());
-
-    // Update expired status of swiped user
-    if let Some(mut swiped_user_profile) = state.user_profiles.remove(&receiver_id) {
-        swiped_user_profile.expired = true;
-        state.user_profiles.insert(receiver_id.clone(), swiped_user_profile);
-    } else {
-        return Err(format!("Failed to update expired status for user ID {}", receiver_id));
-    }
+    state.save_profile(sender_id.clone(), swiping_user_profile.clone());
 
     ic_cdk::println!("Rightswiped! Swiping user profile: {:?}", swiping_user_profile);
     Ok("Right swiped successfully.".to_string())
 }
 
-pub fn fetch_leftswipes(state: &State, user_id: String, pagination: Pagination) -> Result<MatchResult, String> {
+pub fn fetch_leftswipes(state: &State, user_id: String, pagination: Pagination) -> Result<SwipedProfiles, DDateError> {
     ic_cdk::println!("Fetching leftswipes for user ID: {}", user_id);
 
-    let user_profile = state.user_profiles.get(&user_id).ok_or_else(|| format!("User ID '{}' not found", user_id))?;
+    let user_profile = state.user_profiles.get(&user_id).ok_or_else(|| DDateError::NotFound(format!("User ID '{}' not found", user_id)))?;
 
-    if !user_profile.status {
-        return Err("Account is inactive".to_string());
+    if !user_profile.account_state.is_usable() {
+        return Err(DDateError::Inactive("Account is inactive".to_string()));
     }
 
-    if pagination.page == 0 {
-        return Err("Page number must be greater than 0".to_string());
-    }
-    if pagination.size == 0 {
-        return Err("Page size must be greater than 0".to_string());
-    }
+    state.validate_pagination(&pagination)?;
 
     let all_leftswipes: Vec<UserProfileCreationInfo> = user_profile
         .params
@@ -188,7 +214,7 @@ pub fn fetch_leftswipes(state: &State, user_id: String, pagination: Pagination)
         .iter()
         .filter_map(|receiver_id| {
             let swiped_profile = state.user_profiles.get(receiver_id)?;
-            if swiped_profile.status {
+            if swiped_profile.account_state.is_usable() {
                 Some(swiped_profile.clone())
             } else {
                 None
@@ -202,35 +228,31 @@ pub fn fetch_leftswipes(state: &State, user_id: String, pagination: Pagination)
     let end = std::cmp::min(start + pagination.size, total_matches);
 
     if start >= total_matches {
-        return Err("Page number out of range".to_string());
+        return Err(DDateError::InvalidInput("Page number out of range".to_string()));
     }
 
-    let paginated_profiles = all_leftswipes[start..end].to_vec();
+    let paginated_profiles = all_leftswipes[start..end].iter()
+        .map(|profile| PublicProfile::new(profile, audience_for(&user_profile, profile)))
+        .collect();
 
-    Ok(MatchResult {
-        total_matches,
+    Ok(SwipedProfiles {
+        total_profiles: total_matches,
         paginated_profiles,
-        error_message: None,
     })
 }
 
 
 
-pub fn fetch_rightswipes(state: &State, user_id: String, pagination: Pagination) -> Result<MatchResult, String> {
+pub fn fetch_rightswipes(state: &State, user_id: String, pagination: Pagination) -> Result<SwipedProfiles, DDateError> {
     ic_cdk::println!("Fetching rightswipes for user ID: {}", user_id);
 
-    let user_profile = state.user_profiles.get(&user_id).ok_or_else(|| format!("User ID '{}' not found", user_id))?;
+    let user_profile = state.user_profiles.get(&user_id).ok_or_else(|| DDateError::NotFound(format!("User ID '{}' not found", user_id)))?;
 
-    if !user_profile.status {
-        return Err("Account is inactive".to_string());
+    if !user_profile.account_state.is_usable() {
+        return Err(DDateError::Inactive("Account is inactive".to_string()));
     }
 
-    if pagination.page == 0 {
-        return Err("Page number must be greater than 0".to_string());
-    }
-    if pagination.size == 0 {
-        return Err("Page size must be greater than 0".to_string());
-    }
+    state.validate_pagination(&pagination)?;
 
     let all_rightswipes: Vec<UserProfileCreationInfo> = user_profile
         .params
@@ -240,7 +262,7 @@ pub fn fetch_rightswipes(state: &State, user_id: String, pagination: Pagination)
         .iter()
         .filter_map(|receiver_id| {
             let swiped_profile = state.user_profiles.get(receiver_id)?;
-            if swiped_profile.status {
+            if swiped_profile.account_state.is_usable() {
                 Some(swiped_profile.clone())
             } else {
                 None
@@ -254,15 +276,16 @@ pub fn fetch_rightswipes(state: &State, user_id: String, pagination: Pagination)
     let end = std::cmp::min(start + pagination.size, total_matches);
 
     if start >= total_matches {
-        return Err("Page number out of range".to_string());
+        return Err(DDateError::InvalidInput("Page number out of range".to_string()));
     }
 
-    let paginated_profiles = all_rightswipes[start..end].to_vec();
+    let paginated_profiles = all_rightswipes[start..end].iter()
+        .map(|profile| PublicProfile::new(profile, audience_for(&user_profile, profile)))
+        .collect();
 
-    Ok(MatchResult {
-        total_matches,
+    Ok(SwipedProfiles {
+        total_profiles: total_matches,
         paginated_profiles,
-        error_message: None,
     })
 }
 
@@ -274,7 +297,7 @@ pub fn check_for_match(
     let _ = like_profile(state, current_user_id.clone(), liked_user_id.clone());
 
     if let Some(current_user_profile) = state.user_profiles.get(&current_user_id) {
-        if !current_user_profile.status {
+        if !current_user_profile.account_state.is_usable() {
             ic_cdk::println!("Current user's account is inactive");
             return false;
         }
@@ -283,7 +306,7 @@ pub fn check_for_match(
     }
 
     if let Some(liked_user_profile) = state.user_profiles.get(&liked_user_id) {
-        if !liked_user_profile.status {
+        if !liked_user_profile.account_state.is_discoverable() {
             ic_cdk::println!("Liked user's account is inactive");
             return false;
         }
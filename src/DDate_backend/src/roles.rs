@@ -0,0 +1,105 @@
+use candid::{CandidType, Principal};
+use ic_cdk::{caller, query, update};
+use serde::{Deserialize, Serialize};
+
+use crate::cascade::schedule_deletion_tick;
+use crate::errors::DDateError;
+use crate::guards::{is_admin, is_anonymous, is_controller};
+use crate::profile_creation::UserProfileCreationInfo;
+use crate::state_handler::{mutate_state, read_state, Candid, State};
+
+// Declared from least to most privileged so that `Ord` follows the hierarchy.
+#[derive(Debug, Serialize, Clone, Copy, Deserialize, CandidType, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Role {
+    Moderator,
+    Admin,
+    Controller,
+}
+
+#[derive(Debug, Serialize, Clone, Deserialize, CandidType)]
+pub struct RoleAssignment {
+    pub principal: Principal,
+    pub role: Role,
+    pub assigned_by: Principal,
+    pub assigned_at: u64,
+}
+
+impl State {
+    // Canister controllers always hold the Controller role; everyone else needs a stored assignment.
+    pub fn role_of(&self, principal: &Principal) -> Option<Role> {
+        if ic_cdk::api::is_controller(principal) {
+            return Some(Role::Controller);
+        }
+        self.roles.get(principal).map(|assignment| assignment.role)
+    }
+
+    pub fn has_role(&self, principal: &Principal, required: Role) -> bool {
+        self.role_of(principal).is_some_and(|role| role >= required)
+    }
+
+    pub fn assign_role(&mut self, principal: Principal, role: Role, assigned_by: Principal, now: u64) -> Result<String, DDateError> {
+        if role == Role::Controller {
+            return Err(DDateError::InvalidInput("The Controller role is managed through the canister's controller list".to_string()));
+        }
+        if principal == Principal::anonymous() {
+            return Err(DDateError::InvalidInput("The anonymous principal cannot hold a role".to_string()));
+        }
+
+        let assignment = RoleAssignment {
+            principal,
+            role,
+            assigned_by,
+            assigned_at: now,
+        };
+        self.roles.insert(principal, Candid(assignment));
+        ic_cdk::println!("Assigned role {:?} to principal {}", role, principal);
+        Ok(format!("Assigned role {:?} to principal {}", role, principal))
+    }
+
+    pub fn revoke_role(&mut self, principal: &Principal) -> Result<String, DDateError> {
+        match self.roles.remove(principal) {
+            Some(assignment) => Ok(format!("Revoked role {:?} from principal {}", assignment.role, principal)),
+            None => Err(DDateError::NotFound(format!("Principal {} has no assigned role", principal))),
+        }
+    }
+}
+
+#[update(guard = "is_controller")]
+pub fn assign_role(principal: Principal, role: Role) -> Result<String, DDateError> {
+    let assigned_by = caller();
+    mutate_state(|state| state.assign_role(principal, role, assigned_by, ic_cdk::api::time()))
+}
+
+#[update(guard = "is_controller")]
+pub fn revoke_role(principal: Principal) -> Result<String, DDateError> {
+    mutate_state(|state| state.revoke_role(&principal))
+}
+
+#[query(guard = "is_controller")]
+pub fn list_roles() -> Vec<RoleAssignment> {
+    read_state(|state| state.roles.iter().map(|(_, assignment)| assignment.0).collect())
+}
+
+#[query(guard = "is_anonymous")]
+pub fn get_my_role() -> Option<Role> {
+    let principal = caller();
+    read_state(|state| state.role_of(&principal))
+}
+
+#[query(guard = "is_admin")]
+fn get_all() -> Result<(usize, Vec<(String, UserProfileCreationInfo)>), DDateError> {
+    read_state(|state| state.get_all_profiles())
+}
+
+#[update(guard = "is_admin")]
+pub fn admin_delete_account(user_id: String) -> Result<String, DDateError> {
+    ic_cdk::println!("Admin {} deleting account with user_id: {}", caller(), user_id);
+    mutate_state(|state| {
+        if !state.user_profiles.contains_key(&user_id) {
+            return Err(DDateError::NotFound("Profile not found".to_string()));
+        }
+        state.start_cascading_delete(&user_id, ic_cdk::api::time());
+        Ok(format!("User profile deleted with id: {}", user_id))
+    })
+    .inspect(|_| schedule_deletion_tick())
+}
@@ -1,30 +1,86 @@
 
 use ic_stable_structures::memory_manager::{MemoryId, MemoryManager, VirtualMemory};
 use ic_stable_structures::DefaultMemoryImpl;
-use ic_stable_structures::StableBTreeMap;
+use ic_stable_structures::{StableBTreeMap, StableCell};
 use serde::Deserialize;
 use std::cell::RefCell;
 use std::collections::VecDeque;
 use std::ops::{Deref, DerefMut};
 use ic_stable_structures::{storable::Bound,Storable};
-use candid::{CandidType, Decode, Encode};
+use candid::{CandidType, Principal};
 use std::borrow::Cow;
-use crate::profile_creation::{Message, UserProfileCreationInfo};
+use crate::audit::{AuditEntry, AuditKey};
+use crate::cascade::DeletionJob;
+use crate::config::CanisterConfig;
+use crate::data_export::DataExport;
+use crate::device_links::{LinkCode, LinkedPrincipal};
+use crate::migrations::{decode_profile, encode_profile};
+use crate::onboarding::OnboardingDraft;
+use crate::profile_creation::{Message, Notification, UserProfileCreationInfo};
+use crate::recovery::RecoveryRecord;
+use crate::right_and_left_swipe::SwipeQuota;
+use crate::roles::RoleAssignment;
+use crate::upgrade::UpgradeRecord;
 
 
 
 pub type Memory = VirtualMemory<DefaultMemoryImpl>;
 pub type UserProfiles = StableBTreeMap<String, UserProfileCreationInfo, Memory>;
 pub type UserMessages = StableBTreeMap<String,Candid<VecDeque<Message>>,Memory>;
+pub type PrincipalIndex = StableBTreeMap<Principal, String, Memory>;
+pub type LinkedPrincipals = StableBTreeMap<String, Candid<Vec<LinkedPrincipal>>, Memory>;
+pub type LinkCodes = StableBTreeMap<String, Candid<LinkCode>, Memory>;
+pub type RecoveryRecords = StableBTreeMap<String, Candid<RecoveryRecord>, Memory>;
+pub type Roles = StableBTreeMap<Principal, Candid<RoleAssignment>, Memory>;
+pub type ConfigCell = StableCell<Candid<CanisterConfig>, Memory>;
+pub type SwipeQuotas = StableBTreeMap<String, Candid<SwipeQuota>, Memory>;
+pub type UpgradeCell = StableCell<Candid<UpgradeRecord>, Memory>;
+// One blob per user, as written before entries were keyed by sequence number; emptied by post_upgrade.
+pub type LegacyAuditLog = StableBTreeMap<String, Candid<Vec<AuditEntry>>, Memory>;
+pub type OnboardingDrafts = StableBTreeMap<Principal, Candid<OnboardingDraft>, Memory>;
+pub type DeletionJobs = StableBTreeMap<String, Candid<DeletionJob>, Memory>;
+pub type DataExports = StableBTreeMap<String, Candid<DataExport>, Memory>;
+pub type PrincipalNotices = StableBTreeMap<Principal, Candid<VecDeque<Notification>>, Memory>;
+pub type AuditLog = StableBTreeMap<AuditKey, Candid<AuditEntry>, Memory>;
+pub type AuditKeyCell = StableCell<Candid<Vec<u8>>, Memory>;
 
 const PROFILE_DATA: MemoryId = MemoryId::new(0);
 const MESSAGE_DATA: MemoryId = MemoryId::new(1);
+const PRINCIPAL_INDEX_DATA: MemoryId = MemoryId::new(2);
+const LINKED_PRINCIPAL_DATA: MemoryId = MemoryId::new(3);
+const LINK_CODE_DATA: MemoryId = MemoryId::new(4);
+const RECOVERY_DATA: MemoryId = MemoryId::new(5);
+const ROLE_DATA: MemoryId = MemoryId::new(6);
+const CONFIG_DATA: MemoryId = MemoryId::new(7);
+const SWIPE_QUOTA_DATA: MemoryId = MemoryId::new(8);
+const UPGRADE_DATA: MemoryId = MemoryId::new(9);
+const AUDIT_LOG_DATA: MemoryId = MemoryId::new(10);
+const ONBOARDING_DRAFT_DATA: MemoryId = MemoryId::new(11);
+const DELETION_JOB_DATA: MemoryId = MemoryId::new(12);
+const DATA_EXPORT_DATA: MemoryId = MemoryId::new(13);
+const PRINCIPAL_NOTICE_DATA: MemoryId = MemoryId::new(14);
+const AUDIT_ENTRY_DATA: MemoryId = MemoryId::new(15);
+const AUDIT_KEY_DATA: MemoryId = MemoryId::new(16);
 
 pub struct State {
 
     pub user_profiles : UserProfiles,
     pub user_messages : UserMessages,
-    
+    pub principal_index : PrincipalIndex,
+    pub linked_principals : LinkedPrincipals,
+    pub link_codes : LinkCodes,
+    pub recovery_records : RecoveryRecords,
+    pub roles : Roles,
+    pub config : ConfigCell,
+    pub swipe_quotas : SwipeQuotas,
+    pub upgrade_record : UpgradeCell,
+    pub legacy_audit_log : LegacyAuditLog,
+    pub onboarding_drafts : OnboardingDrafts,
+    pub deletion_jobs : DeletionJobs,
+    pub data_exports : DataExports,
+    pub principal_notices : PrincipalNotices,
+    pub audit_log : AuditLog,
+    pub audit_key : AuditKeyCell,
 
 }
 
@@ -37,6 +93,24 @@ thread_local! {
         MEMORY_MANAGER.with(|mm| State {
             user_profiles:UserProfiles::init(mm.borrow().get(PROFILE_DATA)),
             user_messages:UserMessages::init(mm.borrow().get(MESSAGE_DATA)),
+            principal_index:PrincipalIndex::init(mm.borrow().get(PRINCIPAL_INDEX_DATA)),
+            linked_principals:LinkedPrincipals::init(mm.borrow().get(LINKED_PRINCIPAL_DATA)),
+            link_codes:LinkCodes::init(mm.borrow().get(LINK_CODE_DATA)),
+            recovery_records:RecoveryRecords::init(mm.borrow().get(RECOVERY_DATA)),
+            roles:Roles::init(mm.borrow().get(ROLE_DATA)),
+            config:ConfigCell::init(mm.borrow().get(CONFIG_DATA), Candid(CanisterConfig::default()))
+                .expect("failed to initialize the config cell"),
+            swipe_quotas:SwipeQuotas::init(mm.borrow().get(SWIPE_QUOTA_DATA)),
+            upgrade_record:UpgradeCell::init(mm.borrow().get(UPGRADE_DATA), Candid(UpgradeRecord::default()))
+                .expect("failed to initialize the upgrade record cell"),
+            legacy_audit_log:LegacyAuditLog::init(mm.borrow().get(AUDIT_LOG_DATA)),
+            onboarding_drafts:OnboardingDrafts::init(mm.borrow().get(ONBOARDING_DRAFT_DATA)),
+            deletion_jobs:DeletionJobs::init(mm.borrow().get(DELETION_JOB_DATA)),
+            data_exports:DataExports::init(mm.borrow().get(DATA_EXPORT_DATA)),
+            principal_notices:PrincipalNotices::init(mm.borrow().get(PRINCIPAL_NOTICE_DATA)),
+            audit_log:AuditLog::init(mm.borrow().get(AUDIT_ENTRY_DATA)),
+            audit_key:AuditKeyCell::init(mm.borrow().get(AUDIT_KEY_DATA), Candid(Vec::new()))
+                .expect("failed to initialize the audit key cell"),
         })
     );
 }
@@ -58,6 +132,66 @@ pub fn get_messagedata_memory() -> Memory {
     MEMORY_MANAGER.with(|m| m.borrow().get(MESSAGE_DATA))
 }
 
+pub fn get_principalindex_memory() -> Memory {
+    MEMORY_MANAGER.with(|m| m.borrow().get(PRINCIPAL_INDEX_DATA))
+}
+
+pub fn get_linkedprincipal_memory() -> Memory {
+    MEMORY_MANAGER.with(|m| m.borrow().get(LINKED_PRINCIPAL_DATA))
+}
+
+pub fn get_linkcode_memory() -> Memory {
+    MEMORY_MANAGER.with(|m| m.borrow().get(LINK_CODE_DATA))
+}
+
+pub fn get_recovery_memory() -> Memory {
+    MEMORY_MANAGER.with(|m| m.borrow().get(RECOVERY_DATA))
+}
+
+pub fn get_role_memory() -> Memory {
+    MEMORY_MANAGER.with(|m| m.borrow().get(ROLE_DATA))
+}
+
+pub fn get_config_memory() -> Memory {
+    MEMORY_MANAGER.with(|m| m.borrow().get(CONFIG_DATA))
+}
+
+pub fn get_swipequota_memory() -> Memory {
+    MEMORY_MANAGER.with(|m| m.borrow().get(SWIPE_QUOTA_DATA))
+}
+
+pub fn get_upgrade_memory() -> Memory {
+    MEMORY_MANAGER.with(|m| m.borrow().get(UPGRADE_DATA))
+}
+
+pub fn get_auditlog_memory() -> Memory {
+    MEMORY_MANAGER.with(|m| m.borrow().get(AUDIT_LOG_DATA))
+}
+
+pub fn get_onboardingdraft_memory() -> Memory {
+    MEMORY_MANAGER.with(|m| m.borrow().get(ONBOARDING_DRAFT_DATA))
+}
+
+pub fn get_deletionjob_memory() -> Memory {
+    MEMORY_MANAGER.with(|m| m.borrow().get(DELETION_JOB_DATA))
+}
+
+pub fn get_dataexport_memory() -> Memory {
+    MEMORY_MANAGER.with(|m| m.borrow().get(DATA_EXPORT_DATA))
+}
+
+pub fn get_principalnotice_memory() -> Memory {
+    MEMORY_MANAGER.with(|m| m.borrow().get(PRINCIPAL_NOTICE_DATA))
+}
+
+pub fn get_auditentry_memory() -> Memory {
+    MEMORY_MANAGER.with(|m| m.borrow().get(AUDIT_ENTRY_DATA))
+}
+
+pub fn get_auditkey_memory() -> Memory {
+    MEMORY_MANAGER.with(|m| m.borrow().get(AUDIT_KEY_DATA))
+}
+
 
 
 
@@ -66,7 +200,22 @@ impl State {
         Self {
             
             user_profiles: init_file_contents(),
-            user_messages: post_file_contents()
+            user_messages: post_file_contents(),
+            principal_index: principal_index_contents(),
+            linked_principals: linked_principals_contents(),
+            link_codes: link_codes_contents(),
+            recovery_records: recovery_records_contents(),
+            roles: roles_contents(),
+            config: config_contents(),
+            swipe_quotas: swipe_quotas_contents(),
+            upgrade_record: upgrade_record_contents(),
+            legacy_audit_log: legacy_audit_log_contents(),
+            onboarding_drafts: onboarding_drafts_contents(),
+            deletion_jobs: deletion_jobs_contents(),
+            data_exports: data_exports_contents(),
+            principal_notices: principal_notices_contents(),
+            audit_log: audit_log_contents(),
+            audit_key: audit_key_contents(),
         }
     }
 }
@@ -88,18 +237,98 @@ pub fn post_file_contents() -> StableBTreeMap<String,Candid<VecDeque<Message>>,M
 
 }
 
+pub fn principal_index_contents() -> PrincipalIndex {
+    StableBTreeMap::init(get_principalindex_memory())
+}
+
+pub fn linked_principals_contents() -> LinkedPrincipals {
+    StableBTreeMap::init(get_linkedprincipal_memory())
+}
+
+pub fn link_codes_contents() -> LinkCodes {
+    StableBTreeMap::init(get_linkcode_memory())
+}
+
+pub fn recovery_records_contents() -> RecoveryRecords {
+    StableBTreeMap::init(get_recovery_memory())
+}
+
+pub fn roles_contents() -> Roles {
+    StableBTreeMap::init(get_role_memory())
+}
+
+pub fn config_contents() -> ConfigCell {
+    StableCell::init(get_config_memory(), Candid(CanisterConfig::default()))
+        .expect("failed to initialize the config cell")
+}
+
+pub fn swipe_quotas_contents() -> SwipeQuotas {
+    StableBTreeMap::init(get_swipequota_memory())
+}
+
+pub fn upgrade_record_contents() -> UpgradeCell {
+    StableCell::init(get_upgrade_memory(), Candid(UpgradeRecord::default()))
+        .expect("failed to initialize the upgrade record cell")
+}
+
+pub fn legacy_audit_log_contents() -> LegacyAuditLog {
+    StableBTreeMap::init(get_auditlog_memory())
+}
+
+pub fn onboarding_drafts_contents() -> OnboardingDrafts {
+    StableBTreeMap::init(get_onboardingdraft_memory())
+}
+
+pub fn deletion_jobs_contents() -> DeletionJobs {
+    StableBTreeMap::init(get_deletionjob_memory())
+}
+
+pub fn data_exports_contents() -> DataExports {
+    StableBTreeMap::init(get_dataexport_memory())
+}
+
+pub fn principal_notices_contents() -> PrincipalNotices {
+    StableBTreeMap::init(get_principalnotice_memory())
+}
+
+pub fn audit_log_contents() -> AuditLog {
+    StableBTreeMap::init(get_auditentry_memory())
+}
+
+pub fn audit_key_contents() -> AuditKeyCell {
+    StableCell::init(get_auditkey_memory(), Candid(Vec::new()))
+        .expect("failed to initialize the audit key cell")
+}
+
+// Profiles are stored with a version header; see `migrations` for the layout and upgrade path.
 impl Storable for UserProfileCreationInfo{
-    fn to_bytes(&self) -> std::borrow::Cow<[u8]> {
-        Cow::Owned(Encode!(self).unwrap())
+    fn to_bytes(&self) -> std::borrow::Cow<'_, [u8]> {
+        Cow::Owned(encode_profile(self))
     }
 
     fn from_bytes(bytes: std::borrow::Cow<[u8]>) -> Self {
-        Decode!(bytes.as_ref(), Self).unwrap()
+        decode_profile(bytes.as_ref())
+            .unwrap_or_else(|e| ic_cdk::trap(&format!("Failed to decode stored profile: {}", e)))
     }
 
     const BOUND: Bound = Bound::Unbounded;
 }
 
+// Undecoded stored value, for code that has to look at the bytes without knowing their schema.
+pub struct RawBytes(pub Vec<u8>);
+
+impl Storable for RawBytes {
+    const BOUND: Bound = Bound::Unbounded;
+
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Borrowed(&self.0)
+    }
+
+    fn from_bytes(bytes: Cow<'_, [u8]>) -> Self {
+        RawBytes(bytes.into_owned())
+    }
+}
+
 #[derive(Default)]
 pub struct Candid<T>(pub T)
 where
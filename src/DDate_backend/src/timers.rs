@@ -0,0 +1,18 @@
+use crate::age::start_age_refresh_timer;
+use crate::audit::start_audit_key_timer;
+use crate::cascade::start_deletion_timer;
+use crate::data_export::start_export_purge_timer;
+use crate::deletion::start_scheduled_deletion_timers;
+use crate::onboarding::start_draft_purge_timer;
+use crate::pause::start_pause_timers;
+
+// Timers are not kept across upgrades, so `init` and `post_upgrade` both call this.
+pub fn start_timers() {
+    start_audit_key_timer();
+    start_age_refresh_timer();
+    start_draft_purge_timer();
+    start_pause_timers();
+    start_deletion_timer();
+    start_scheduled_deletion_timers();
+    start_export_purge_timer();
+}
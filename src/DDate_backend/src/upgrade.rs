@@ -0,0 +1,221 @@
+use candid::{CandidType, Principal};
+use ic_cdk::{pre_upgrade, query};
+use ic_stable_structures::{StableBTreeMap, StableCell, Storable};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::audit::AuditKey;
+use crate::guards::is_admin;
+use crate::state_handler::{
+    get_auditentry_memory, get_auditkey_memory, get_auditlog_memory, get_config_memory, get_dataexport_memory, get_deletionjob_memory, get_principalnotice_memory, get_onboardingdraft_memory, get_linkcode_memory, get_linkedprincipal_memory, get_messagedata_memory,
+    get_principalindex_memory, get_profiledata_memory, get_recovery_memory, get_role_memory, get_swipequota_memory,
+    mutate_state, read_state, Candid, Memory, RawBytes, State,
+};
+
+#[derive(Debug, Serialize, Clone, Deserialize, CandidType, PartialEq)]
+pub struct MemoryFingerprint {
+    pub memory_id: u8,
+    pub name: String,
+    pub entries: u64,
+    // Hex SHA-256 over every stored key and value, in key order.
+    pub checksum: String,
+}
+
+#[derive(Debug, Serialize, Clone, Deserialize, CandidType)]
+pub struct StateFingerprint {
+    pub taken_at: u64,
+    pub memories: Vec<MemoryFingerprint>,
+}
+
+#[derive(Debug, Serialize, Clone, Deserialize, CandidType, PartialEq)]
+pub enum IntegrityCheck {
+    Intact,
+    Mismatch,
+    // The previous version had no pre_upgrade hook, so there was nothing to compare against.
+    NoBaseline,
+    // Stable memory was too large to fingerprint within the instruction budget, before or after the upgrade.
+    Skipped,
+}
+
+#[derive(Debug, Serialize, Clone, Deserialize, CandidType)]
+pub struct MemoryMismatch {
+    pub before: MemoryFingerprint,
+    pub after: Option<MemoryFingerprint>,
+}
+
+#[derive(Debug, Serialize, Clone, Deserialize, CandidType)]
+pub struct UpgradeVerification {
+    pub verified_at: u64,
+    pub fingerprinted_at: Option<u64>,
+    pub result: IntegrityCheck,
+    pub memories: Vec<MemoryFingerprint>,
+    pub mismatches: Vec<MemoryMismatch>,
+}
+
+#[derive(Debug, Serialize, Clone, Default, Deserialize, CandidType)]
+pub struct UpgradeRecord {
+    // Written by pre_upgrade and consumed by the next post_upgrade.
+    pub pending: Option<StateFingerprint>,
+    // Set instead of `pending` when pre_upgrade ran out of budget.
+    pub skipped_at: Option<u64>,
+    pub last_verification: Option<UpgradeVerification>,
+}
+
+// Instructions one fingerprint of the whole state may take. An upgrade gets 300B for both hooks together,
+// so each side stays at a sixth of that and leaves the rest for migrations.
+const FINGERPRINT_INSTRUCTION_BUDGET: u64 = 50_000_000_000;
+
+// Returns None once the instruction counter passes `deadline`, instead of running into the upgrade limit.
+fn fingerprint(
+    memory_id: u8,
+    name: &str,
+    entries: impl Iterator<Item = (Vec<u8>, Vec<u8>)>,
+    deadline: u64,
+) -> Option<MemoryFingerprint> {
+    let mut hasher = Sha256::new();
+    let mut count = 0;
+    for (key, value) in entries {
+        hasher.update((key.len() as u64).to_le_bytes());
+        hasher.update(&key);
+        hasher.update((value.len() as u64).to_le_bytes());
+        hasher.update(&value);
+        count += 1;
+        if ic_cdk::api::instruction_counter() > deadline {
+            return None;
+        }
+    }
+    Some(MemoryFingerprint {
+        memory_id,
+        name: name.to_string(),
+        entries: count,
+        checksum: format!("{:x}", hasher.finalize()),
+    })
+}
+
+// Values are read undecoded so the fingerprint survives schema changes between the two versions.
+fn fingerprint_map<K>(memory_id: u8, name: &str, memory: Memory, deadline: u64) -> Option<MemoryFingerprint>
+where
+    K: Storable + Ord + Clone,
+{
+    let map: StableBTreeMap<K, RawBytes, Memory> = StableBTreeMap::init(memory);
+    fingerprint(
+        memory_id,
+        name,
+        map.iter().map(|(key, value)| (key.to_bytes().into_owned(), value.0)),
+        deadline,
+    )
+}
+
+fn fingerprint_cell(memory_id: u8, name: &str, memory: Memory, deadline: u64) -> Option<MemoryFingerprint> {
+    let cell = StableCell::init(memory, RawBytes(Vec::new())).expect("failed to read the cell");
+    fingerprint(memory_id, name, std::iter::once((Vec::new(), cell.get().0.clone())), deadline)
+}
+
+// Covers every MemoryId in state_handler except the upgrade record itself. Takes `&State` so
+// the typed maps are initialized before their memories are opened raw. None if the state is too
+// large to hash within `FINGERPRINT_INSTRUCTION_BUDGET`.
+pub fn fingerprint_state(_state: &State, now: u64) -> Option<StateFingerprint> {
+    let deadline = ic_cdk::api::instruction_counter().saturating_add(FINGERPRINT_INSTRUCTION_BUDGET);
+    Some(StateFingerprint {
+        taken_at: now,
+        memories: vec![
+            fingerprint_map::<String>(0, "user_profiles", get_profiledata_memory(), deadline)?,
+            fingerprint_map::<String>(1, "user_messages", get_messagedata_memory(), deadline)?,
+            fingerprint_map::<Principal>(2, "principal_index", get_principalindex_memory(), deadline)?,
+            fingerprint_map::<String>(3, "linked_principals", get_linkedprincipal_memory(), deadline)?,
+            fingerprint_map::<String>(4, "link_codes", get_linkcode_memory(), deadline)?,
+            fingerprint_map::<String>(5, "recovery_records", get_recovery_memory(), deadline)?,
+            fingerprint_map::<Principal>(6, "roles", get_role_memory(), deadline)?,
+            fingerprint_cell(7, "config", get_config_memory(), deadline)?,
+            fingerprint_map::<String>(8, "swipe_quotas", get_swipequota_memory(), deadline)?,
+            fingerprint_map::<String>(10, "audit_log", get_auditlog_memory(), deadline)?,
+            fingerprint_map::<Principal>(11, "onboarding_drafts", get_onboardingdraft_memory(), deadline)?,
+            fingerprint_map::<String>(12, "deletion_jobs", get_deletionjob_memory(), deadline)?,
+            fingerprint_map::<String>(13, "data_exports", get_dataexport_memory(), deadline)?,
+            fingerprint_map::<Principal>(14, "principal_notices", get_principalnotice_memory(), deadline)?,
+            fingerprint_map::<AuditKey>(15, "audit_entries", get_auditentry_memory(), deadline)?,
+            fingerprint_cell(16, "audit_key", get_auditkey_memory(), deadline)?,
+        ],
+    })
+}
+
+impl State {
+    fn upgrade_record(&self) -> UpgradeRecord {
+        self.upgrade_record.get().0.clone()
+    }
+
+    fn set_upgrade_record(&mut self, record: UpgradeRecord) {
+        self.upgrade_record
+            .set(Candid(record))
+            .unwrap_or_else(|e| ic_cdk::trap(&format!("Failed to store upgrade record: {:?}", e)));
+    }
+
+    // `fingerprint` of None records that pre_upgrade skipped the check.
+    pub fn record_pre_upgrade_fingerprint(&mut self, fingerprint: Option<StateFingerprint>, now: u64) {
+        let mut record = self.upgrade_record();
+        record.skipped_at = fingerprint.is_none().then_some(now);
+        record.pending = fingerprint;
+        self.set_upgrade_record(record);
+    }
+
+    // Compares the fingerprint taken before the upgrade with `after` and keeps the outcome for `get_upgrade_status`.
+    pub fn verify_upgrade(&mut self, after: Option<StateFingerprint>, now: u64) -> UpgradeVerification {
+        let mut record = self.upgrade_record();
+        let before = record.pending.take();
+        let skipped_at = record.skipped_at.take();
+
+        let mismatches: Vec<MemoryMismatch> = match (&before, &after) {
+            (Some(before), Some(after)) => before
+                .memories
+                .iter()
+                .filter_map(|before| {
+                    let after = after.memories.iter().find(|m| m.memory_id == before.memory_id);
+                    if after == Some(before) {
+                        return None;
+                    }
+                    Some(MemoryMismatch {
+                        before: before.clone(),
+                        after: after.cloned(),
+                    })
+                })
+                .collect(),
+            _ => Vec::new(),
+        };
+
+        let result = match (&before, &after) {
+            (_, None) => IntegrityCheck::Skipped,
+            (None, Some(_)) if skipped_at.is_some() => IntegrityCheck::Skipped,
+            (None, Some(_)) => IntegrityCheck::NoBaseline,
+            (Some(_), Some(_)) if mismatches.is_empty() => IntegrityCheck::Intact,
+            (Some(_), Some(_)) => IntegrityCheck::Mismatch,
+        };
+        let verification = UpgradeVerification {
+            verified_at: now,
+            fingerprinted_at: before.map(|before| before.taken_at),
+            result,
+            memories: after.map(|after| after.memories).unwrap_or_default(),
+            mismatches,
+        };
+
+        record.last_verification = Some(verification.clone());
+        self.set_upgrade_record(record);
+        verification
+    }
+}
+
+#[pre_upgrade]
+fn pre_upgrade() {
+    let now = ic_cdk::api::time();
+    mutate_state(|state| {
+        let fingerprint = fingerprint_state(state, now);
+        if fingerprint.is_none() {
+            ic_cdk::println!("State too large to fingerprint before the upgrade; skipping the integrity check");
+        }
+        state.record_pre_upgrade_fingerprint(fingerprint, now);
+    });
+}
+
+#[query(guard = "is_admin")]
+pub fn get_upgrade_status() -> Option<UpgradeVerification> {
+    read_state(|state| state.upgrade_record().last_verification)
+}
@@ -0,0 +1,339 @@
+use candid::CandidType;
+use serde::{Deserialize, Serialize};
+
+use crate::age::{AgePolicy, Date};
+use crate::attributes::Choice;
+use crate::extended_profile::ExtendedProfile;
+use crate::onboarding::OnboardingStep;
+use crate::profile_creation::{UserInputParams, UserProfileParams};
+
+const MAX_AGE: u64 = 120;
+const MAX_NAME_LENGTH: usize = 50;
+const MAX_EMAIL_LENGTH: usize = 254;
+const MAX_TEXT_LENGTH: usize = 100;
+const MAX_INTRODUCTION_LENGTH: usize = 1000;
+const MAX_LIST_ITEMS: usize = 20;
+const MAX_IMAGES: usize = 6;
+const MIN_HEIGHT_CM: u64 = 90;
+const MAX_HEIGHT_CM: u64 = 250;
+const MIN_PHONE_DIGITS: usize = 7;
+const MAX_PHONE_DIGITS: usize = 15;
+const REQUIRED_FIELDS: [&str; 6] = ["name", "email", "gender", "dob", "min_preferred_age", "max_preferred_age"];
+
+#[derive(Debug, Serialize, Clone, Deserialize, CandidType, PartialEq)]
+pub struct FieldError {
+    pub field: String,
+    pub message: String,
+}
+
+struct Validator {
+    errors: Vec<FieldError>,
+    policy: AgePolicy,
+}
+
+impl Validator {
+    fn new(policy: AgePolicy) -> Self {
+        Validator {
+            errors: Vec::new(),
+            policy,
+        }
+    }
+
+    fn error(&mut self, field: &str, message: impl Into<String>) {
+        self.errors.push(FieldError {
+            field: field.to_string(),
+            message: message.into(),
+        });
+    }
+
+    fn required<T>(&mut self, field: &str, value: &Option<T>) {
+        if value.is_none() {
+            self.error(field, "is required");
+        }
+    }
+
+    fn required_text(&mut self, field: &str, value: &Option<String>) {
+        if value.as_deref().is_none_or(|v| v.trim().is_empty()) {
+            self.error(field, "is required");
+        }
+    }
+
+    fn text(&mut self, field: &str, value: &Option<String>, max_length: usize) {
+        if let Some(value) = value {
+            if value.chars().count() > max_length {
+                self.error(field, format!("must be at most {} characters", max_length));
+            }
+        }
+    }
+
+    // Only `Other` carries free text, so that is all there is to check.
+    fn choice<T: Choice>(&mut self, field: &str, value: &Option<T>) {
+        if let Some(text) = value.as_ref().and_then(Choice::other_text) {
+            if text.trim().is_empty() {
+                self.error(field, "must not be empty");
+            } else if text.chars().count() > MAX_TEXT_LENGTH {
+                self.error(field, format!("must be at most {} characters", MAX_TEXT_LENGTH));
+            }
+        }
+    }
+
+    fn list(&mut self, field: &str, value: &Option<Vec<String>>, max_items: usize, max_length: usize) {
+        if let Some(items) = value {
+            if items.len() > max_items {
+                self.error(field, format!("must contain at most {} entries", max_items));
+            }
+            if items.iter().any(|item| item.trim().is_empty()) {
+                self.error(field, "must not contain empty entries");
+            }
+            if items.iter().any(|item| item.chars().count() > max_length) {
+                self.error(field, format!("entries must be at most {} characters", max_length));
+            }
+        }
+    }
+
+    // Preferred ages share the configured minimum with `dob`, so nobody can ask for profiles the canister never holds.
+    fn age(&mut self, field: &str, value: Option<u64>) {
+        if let Some(age) = value {
+            let min_age = self.policy.min_age;
+            if !(min_age..=MAX_AGE).contains(&age) {
+                self.error(field, format!("must be between {} and {}", min_age, MAX_AGE));
+            }
+        }
+    }
+
+    // `age` is derived from this value, so it has to be a real past date that meets the minimum age.
+    fn dob(&mut self, value: &Option<String>) {
+        if let Some(dob) = value.as_deref().filter(|dob| !dob.trim().is_empty()) {
+            let min_age = self.policy.min_age;
+            match Date::parse(dob).and_then(|date| date.age_on(self.policy.today)) {
+                None => self.error("dob", "must be a past date in YYYY-MM-DD format"),
+                Some(age) if age < min_age => self.error("dob", format!("must show an age of at least {}", min_age)),
+                Some(age) if age > MAX_AGE => self.error("dob", format!("must show an age of at most {}", MAX_AGE)),
+                Some(_) => {}
+            }
+        }
+    }
+
+    fn email(&mut self, value: &Option<String>) {
+        if let Some(email) = value.as_deref().filter(|email| !email.is_empty()) {
+            if email.chars().count() > MAX_EMAIL_LENGTH || !is_valid_email(email) {
+                self.error("email", "must be a valid email address");
+            }
+        }
+    }
+
+    fn phone(&mut self, value: &Option<String>) {
+        if let Some(phone) = value.as_deref().filter(|phone| !phone.is_empty()) {
+            if !is_valid_phone(phone) {
+                self.error(
+                    "mobile_number",
+                    format!("must contain {} to {} digits", MIN_PHONE_DIGITS, MAX_PHONE_DIGITS),
+                );
+            }
+        }
+    }
+
+    fn finish(self) -> Result<(), Vec<FieldError>> {
+        if self.errors.is_empty() {
+            Ok(())
+        } else {
+            Err(self.errors)
+        }
+    }
+}
+
+fn is_valid_email(email: &str) -> bool {
+    if email.chars().any(char::is_whitespace) {
+        return false;
+    }
+    match email.split_once('@') {
+        Some((local, domain)) => {
+            !local.is_empty()
+                && !domain.contains('@')
+                && domain.contains('.')
+                && !domain.starts_with('.')
+                && !domain.ends_with('.')
+        }
+        None => false,
+    }
+}
+
+// Accepts an optional leading '+' followed by digits, spaces and dashes.
+fn is_valid_phone(phone: &str) -> bool {
+    let number = phone.strip_prefix('+').unwrap_or(phone);
+    if !number.chars().all(|c| c.is_ascii_digit() || c == ' ' || c == '-') {
+        return false;
+    }
+    let digits = number.chars().filter(char::is_ascii_digit).count();
+    (MIN_PHONE_DIGITS..=MAX_PHONE_DIGITS).contains(&digits)
+}
+
+// Checks the format and size of every field present in `params`.
+fn validate_fields(validator: &mut Validator, params: &UserInputParams) {
+    validator.text("name", &params.name, MAX_NAME_LENGTH);
+    validator.email(&params.email);
+    validator.phone(&params.mobile_number);
+    validator.dob(&params.dob);
+    validator.choice("gender", &params.gender);
+    validator.choice("gender_pronouns", &params.gender_pronouns);
+    validator.text("life_path_number", &params.life_path_number, MAX_TEXT_LENGTH);
+    validator.choice("religion", &params.religion);
+    validator.choice("zodiac", &params.zodiac);
+    validator.choice("diet", &params.diet);
+    validator.choice("smoking", &params.smoking);
+    validator.choice("drinking", &params.drinking);
+    validator.choice("interests_in", &params.interests_in);
+    validator.text("location_city", &params.location_city, MAX_TEXT_LENGTH);
+    validator.text("location_state", &params.location_state, MAX_TEXT_LENGTH);
+    validator.text("location_country", &params.location_country, MAX_TEXT_LENGTH);
+    validator.text("preferred_city", &params.preferred_city, MAX_TEXT_LENGTH);
+    validator.text("preferred_state", &params.preferred_state, MAX_TEXT_LENGTH);
+    validator.text("preferred_country", &params.preferred_country, MAX_TEXT_LENGTH);
+    validator.text("introduction", &params.introduction, MAX_INTRODUCTION_LENGTH);
+    validator.list("hobbies", &params.hobbies, MAX_LIST_ITEMS, MAX_TEXT_LENGTH);
+    validator.list("sports", &params.sports, MAX_LIST_ITEMS, MAX_TEXT_LENGTH);
+    validator.age("min_preferred_age", params.min_preferred_age);
+    validator.age("max_preferred_age", params.max_preferred_age);
+
+    if let Some(images) = &params.images {
+        if images.len() > MAX_IMAGES {
+            validator.error("images", format!("must contain at most {} images", MAX_IMAGES));
+        }
+        if images.iter().any(|image| image.trim().is_empty()) {
+            validator.error("images", "must not contain empty images");
+        }
+    }
+    if let Some(extended) = &params.extended {
+        validate_extended(validator, extended);
+    }
+}
+
+fn validate_extended(validator: &mut Validator, extended: &ExtendedProfile) {
+    if let Some(height_cm) = extended.height_cm {
+        if !(MIN_HEIGHT_CM..=MAX_HEIGHT_CM).contains(&height_cm) {
+            validator.error("extended.height_cm", format!("must be between {} and {}", MIN_HEIGHT_CM, MAX_HEIGHT_CM));
+        }
+    }
+    validator.text("extended.occupation", &extended.occupation, MAX_TEXT_LENGTH);
+    validator.text("extended.looking_for", &extended.looking_for, MAX_TEXT_LENGTH);
+    validator.text("extended.pets", &extended.pets, MAX_TEXT_LENGTH);
+    validator.list("extended.art_and_culture", &extended.art_and_culture, MAX_LIST_ITEMS, MAX_TEXT_LENGTH);
+    validator.list("extended.general_habits", &extended.general_habits, MAX_LIST_ITEMS, MAX_TEXT_LENGTH);
+    validator.list("extended.outdoor_activities", &extended.outdoor_activities, MAX_LIST_ITEMS, MAX_TEXT_LENGTH);
+    validator.list("extended.travel", &extended.travel, MAX_LIST_ITEMS, MAX_TEXT_LENGTH);
+    validator.list("extended.movies", &extended.movies, MAX_LIST_ITEMS, MAX_TEXT_LENGTH);
+}
+
+fn validate_preferred_age_range(validator: &mut Validator, min: Option<u64>, max: Option<u64>) {
+    if let (Some(min), Some(max)) = (min, max) {
+        if min > max {
+            validator.error("min_preferred_age", "must not be greater than max_preferred_age");
+        }
+    }
+}
+
+pub fn validate_new_profile(params: &UserInputParams, policy: AgePolicy) -> Result<(), Vec<FieldError>> {
+    let mut validator = Validator::new(policy);
+    validator.required_text("name", &params.name);
+    validator.required_text("email", &params.email);
+    validator.required("gender", &params.gender);
+    validator.required_text("dob", &params.dob);
+    validator.required("min_preferred_age", &params.min_preferred_age);
+    validator.required("max_preferred_age", &params.max_preferred_age);
+    validate_fields(&mut validator, params);
+    validate_preferred_age_range(&mut validator, params.min_preferred_age, params.max_preferred_age);
+    validator.finish()
+}
+
+// Fields left as `None` keep their stored value, so cross-field checks look at the merged result.
+fn check_update(validator: &mut Validator, current: &UserProfileParams, params: &UserInputParams) {
+    for (field, value) in [
+        ("name", &params.name),
+        ("email", &params.email),
+        ("dob", &params.dob),
+    ] {
+        if value.as_deref().is_some_and(|v| v.trim().is_empty()) {
+            validator.error(field, "must not be empty");
+        }
+    }
+    validate_fields(validator, params);
+    validate_preferred_age_range(
+        validator,
+        params.min_preferred_age.or(current.min_preferred_age),
+        params.max_preferred_age.or(current.max_preferred_age),
+    );
+}
+
+pub fn validate_profile_update(
+    current: &UserProfileParams,
+    params: &UserInputParams,
+    policy: AgePolicy,
+) -> Result<(), Vec<FieldError>> {
+    let mut validator = Validator::new(policy);
+    check_update(&mut validator, current, params);
+    validator.finish()
+}
+
+// `written` holds the values a patch sets; `cleared` names the fields it removes.
+pub fn validate_profile_patch(
+    current: &UserProfileParams,
+    written: &UserInputParams,
+    cleared: &[&str],
+    policy: AgePolicy,
+) -> Result<(), Vec<FieldError>> {
+    let mut validator = Validator::new(policy);
+    for field in cleared {
+        if REQUIRED_FIELDS.contains(field) {
+            validator.error(field, "is required and cannot be cleared");
+        }
+    }
+    check_update(&mut validator, current, written);
+    validator.finish()
+}
+
+// Each step requires what its form requires; everything it sends is format-checked. `saved` is what
+// earlier steps stored, so the age range is checked against the values the draft will end up with.
+pub fn validate_onboarding_step(
+    step: OnboardingStep,
+    saved: &UserProfileParams,
+    params: &UserInputParams,
+    policy: AgePolicy,
+) -> Result<(), Vec<FieldError>> {
+    let mut validator = Validator::new(policy);
+    match step {
+        OnboardingStep::Basics => {
+            validator.required_text("name", &params.name);
+            validator.required_text("email", &params.email);
+            validator.required("gender", &params.gender);
+            validator.required_text("dob", &params.dob);
+        }
+        OnboardingStep::Preferences => {
+            validator.required("min_preferred_age", &params.min_preferred_age);
+            validator.required("max_preferred_age", &params.max_preferred_age);
+        }
+        OnboardingStep::About | OnboardingStep::Lifestyle | OnboardingStep::Interests | OnboardingStep::Photos => {}
+    }
+    validate_fields(&mut validator, params);
+    validate_preferred_age_range(
+        &mut validator,
+        params.min_preferred_age.or(saved.min_preferred_age),
+        params.max_preferred_age.or(saved.max_preferred_age),
+    );
+    validator.finish()
+}
+
+// Steps were format-checked as they were saved, so this checks the fields an account needs and
+// re-checks `dob` in case the minimum age changed since the draft was started, and the age range as merged
+// across steps.
+pub fn validate_completed_draft(params: &UserProfileParams, policy: AgePolicy) -> Result<(), Vec<FieldError>> {
+    let mut validator = Validator::new(policy);
+    validator.required_text("name", &params.name);
+    validator.required_text("email", &params.email);
+    validator.required("gender", &params.gender);
+    validator.required_text("dob", &params.dob);
+    validator.required("min_preferred_age", &params.min_preferred_age);
+    validator.required("max_preferred_age", &params.max_preferred_age);
+    validator.dob(&params.dob);
+    validate_preferred_age_range(&mut validator, params.min_preferred_age, params.max_preferred_age);
+    validator.finish()
+}